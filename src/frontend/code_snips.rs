@@ -33,19 +33,8 @@ pub const FIZZBUZZ_CODE: &str = "let fizzbuzz = fn(n) {
 
 fizzbuzz(100);";
 
-pub const DOUBLE_W_MAP: &str = "let map = fn(arr, f) {
-    let iter = fn (arr, accumulated) {
-        if (len(arr) == 0) {
-            accumulated
-        } else {
-            iter(rest(arr), push(accumulated, f(first(arr))));
-        }
-    };
-    iter(arr, []);
-};
-
-let a = [1, 2, 3, 4];
+pub const DOUBLE_W_MAP: &str = "let a = [1, 2, 3, 4];
 let double = fn(x) { x * 2 };
 
-puts(\"Before double: \", a);
-puts(\"After double: \", map(a, double));";
+puts(\"Before double: ${a}\");
+puts(\"After double: ${map(a, double)}\");";
@@ -23,6 +23,8 @@ pub fn Repl(engine_type: RwSignal<EngineType>) -> impl IntoView {
     let mut machine = vm::Machine::default();
     let mut ctx = vm::CompiledContext::default();
     let env = Rc::new(evaluator::Env::default());
+    machine.load_prelude(&mut ctx);
+    env.load_prelude();
     view! {
         <EngineSelector engine_type repl=output />
         <pre id="output" class="w-full h-4/5 font-mono bg-gray-100">
@@ -69,15 +71,31 @@ fn run_vm(
 ) {
     let (program, _time) = generate_program(input);
     program.compile(ctx);
+    let errors = ctx.take_errors();
+    if !errors.is_empty() {
+        for e in errors {
+            output.update(|s| s.write_fmt(format_args!("Compile error: {}\n", e)).unwrap());
+        }
+        return;
+    }
     ctx.remove_last_pop();
-    machine.run(ctx.get_constants(), ctx.make_main_closure());
+    if let Err(thrown) = machine.run(ctx.get_constants(), ctx.make_main_closure()) {
+        output.update(|s| s.write_fmt(format_args!("Uncaught {}\n", thrown)).unwrap());
+        return;
+    }
     let binding = machine.get_last_expr();
     output.update(|s| s.write_fmt(format_args!("{}\n", binding)).unwrap());
 }
 
 fn run_eval(input: String, output: RwSignal<String>, env: &Rc<evaluator::Env>) {
     let (program, _time) = generate_program(input);
-    let binding = program.eval(env).unwrap();
+    let binding = match program.eval(env) {
+        Ok(binding) => binding,
+        Err(thrown) => {
+            output.update(|s| s.write_fmt(format_args!("Uncaught {}\n", thrown)).unwrap());
+            return;
+        }
+    };
     logging::log!("{}", binding);
     output.update(|s| s.write_fmt(format_args!("{}\n", binding)).unwrap());
 }
@@ -1,4 +1,4 @@
-use super::utils::{bytecode_engine, eval_engine, generate_program, RunResult};
+use super::utils::{bytecode_engine, eval_engine, generate_program, run_seed, RunResult};
 use super::EngineType;
 use crate::frontend::EngineSelector;
 use leptos::prelude::*;
@@ -25,12 +25,13 @@ pub fn Runner(
                     on:click=move |_| {
                         let (program, timer) = generate_program(text());
                         parse_time.set(timer);
+                        let seed = run_seed();
                         if matches!(engine_type(), EngineType::Both | EngineType::VM) {
-                            let result = bytecode_engine(&program);
+                            let result = bytecode_engine(&program, seed);
                             vm.set(result);
                         }
                         if matches!(engine_type(), EngineType::Both | EngineType::Evaluator) {
-                            let result = eval_engine(&program);
+                            let result = eval_engine(&program, seed);
                             eval.set(result);
                         }
                     }
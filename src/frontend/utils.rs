@@ -156,26 +156,51 @@ pub fn generate_program(text: String) -> (Program, i64) {
     let timer = (Local::now() - timer).num_milliseconds();
     (parser.program().unwrap(), timer)
 }
-pub fn eval_engine(program: &Program) -> RunResult {
-    let env = Rc::new(Env::default());
+//A new seed for every run, both engines get the same one so their random
+//builtins agree
+pub fn run_seed() -> u64 {
+    Local::now().timestamp_millis() as u64
+}
+pub fn eval_engine(program: &Program, seed: u64) -> RunResult {
+    let env = Rc::new(Env::default().with_seed(seed));
+    env.load_prelude();
     let timer = Local::now();
-    let _ = program.eval(&env).unwrap();
+    let result = program.eval(&env);
     let timer = (Local::now() - timer).num_milliseconds();
+    let mut output = format!("{}", env.stdout.borrow());
+    if let Err(thrown) = result {
+        output += &format!("Uncaught {}\n", thrown);
+    }
     RunResult {
-        result: format!("{}", env.stdout.borrow()),
+        result: output,
         time: timer,
     }
 }
 
-pub fn bytecode_engine(program: &Program) -> RunResult {
+pub fn bytecode_engine(program: &Program, seed: u64) -> RunResult {
     let mut ctx = CompiledContext::default();
-    let mut machine = Machine::default();
+    let mut machine = Machine::with_seed(seed);
+    machine.load_prelude(&mut ctx);
     let timer = Local::now();
     program.compile(&mut ctx);
-    machine.run(ctx.get_constants(), ctx.make_main_closure());
+    let errors = ctx.take_errors();
+    if !errors.is_empty() {
+        return RunResult {
+            result: errors
+                .iter()
+                .map(|e| format!("Compile error: {}\n", e))
+                .collect(),
+            time: (Local::now() - timer).num_milliseconds(),
+        };
+    }
+    let result = machine.run(ctx.get_constants(), ctx.make_main_closure());
     let timer = (Local::now() - timer).num_milliseconds();
+    let mut output = machine.get_stdout();
+    if let Err(thrown) = result {
+        output += &format!("Uncaught {}\n", thrown);
+    }
     RunResult {
-        result: machine.get_stdout(),
+        result: output,
         time: timer,
     }
 }
@@ -1,24 +1,28 @@
-use crate::monkey::parser::Fn;
+use crate::monkey::parser::{Fn, HashEntry, Struct, Try};
 use crate::monkey::{
+    indexing::{normalize_index, slice_bounds},
     lexer::Token,
     parser::{Block, Expr, Program},
+    prelude,
+    rng::Rng,
+    stdlib::{equal, sorted_entries, text, Clock, Context, Native, SystemClock, Value},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::iter::zip;
 use std::rc::Rc;
 use std::str::FromStr;
-use std::{cell::RefCell, fmt::Write};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+};
 use Primitive::*;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Builtin {
     Len,
-    First,
-    Last,
-    Rest,
-    Push,
     Puts,
+    Next,
+    Native(Native),
 }
 
 impl FromStr for Builtin {
@@ -26,12 +30,9 @@ impl FromStr for Builtin {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "len" => Ok(Builtin::Len),
-            "first" => Ok(Builtin::First),
-            "last" => Ok(Builtin::Last),
-            "rest" => Ok(Builtin::Rest),
-            "push" => Ok(Builtin::Push),
             "puts" => Ok(Builtin::Puts),
-            _ => Err("Invalid builtin".to_string()),
+            "next" => Ok(Builtin::Next),
+            _ => Native::from_str(s).map(Builtin::Native),
         }
     }
 }
@@ -39,61 +40,45 @@ impl FromStr for Builtin {
 impl Display for Builtin {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Builtin::Len => write!(f, "BUILTIN len"),
-            Builtin::First => write!(f, "BUILTIN first"),
-            Builtin::Last => write!(f, "BUILTIN last"),
-            Builtin::Rest => write!(f, "BUILTIN rest"),
-            Builtin::Push => write!(f, "BUILTIN push"),
-            Builtin::Puts => write!(f, "BUILTIN puts"),
+            Builtin::Len => write!(f, "len"),
+            Builtin::Puts => write!(f, "puts"),
+            Builtin::Next => write!(f, "next"),
+            Builtin::Native(n) => write!(f, "{}", n.name()),
         }
     }
 }
 impl Builtin {
-    fn eval(self, args: &[Binding], stdout: &Rc<RefCell<String>>) -> Result<Binding, &'static str> {
+    fn arity(&self) -> i64 {
         match self {
+            Builtin::Len | Builtin::Next => 1,
+            Builtin::Puts => -1,
+            Builtin::Native(native) => native.arity(),
+        }
+    }
+    fn eval(self, args: &[Binding], env: &Rc<Env>) -> Result<Binding, Binding> {
+        match self {
+            //counts are checked first so the errors read like the vm's
+            Builtin::Len if args.len() != 1 => {
+                Err(error("Expected single argument for len builtin"))
+            }
             Builtin::Len => match args {
-                [Binding::Primitive(String_(s))] => Ok(Binding::Primitive(Int(s.len() as i64))),
-                [Binding::Array(a)] => Ok(Binding::Primitive(Int(a.len() as i64))),
-                _ => Err("Expected single string or array for len builtin"),
-            },
-            Builtin::First => match args {
-                [Binding::Array(a)] => {
-                    if let Some(first) = a.first() {
-                        Ok(first.clone())
-                    } else {
-                        Err("Expected array with at least one element for first builtin")
-                    }
+                [Binding::Primitive(String_(s))] => {
+                    Ok(Binding::Primitive(Int(s.chars().count() as i64)))
                 }
-                _ => Err("Expected array for first builtin"),
-            },
-            Builtin::Last => match args {
-                [Binding::Array(a)] => {
-                    if let Some(last) = a.last() {
-                        Ok(last.clone())
-                    } else {
-                        Err("Expected array with at least one element for last builtin")
-                    }
-                }
-
-                _ => Err("Expected array for last builtin"),
-            },
-            Builtin::Rest => match args {
-                [Binding::Array(a)] => Ok(Binding::Array(a.iter().skip(1).cloned().collect())),
-                _ => Err("Expected array for rest builtin"),
+                [Binding::Array(a)] => Ok(Binding::Primitive(Int(a.len() as i64))),
+                [Binding::Hash(h)] => Ok(Binding::Primitive(Int(h.len() as i64))),
+                _ => Err(error("Expected array, hash or string for len builtin")),
             },
-            Builtin::Push => match args {
-                [Binding::Array(a), new] => {
-                    let mut ret = a.clone();
-                    ret.push(new.clone());
-                    Ok(Binding::Array(ret))
-                }
-                _ => Err("Expected array and int or string for push builtin"),
+            Builtin::Next => match args {
+                [Binding::Generator(gen)] => Ok(resume(gen)?.unwrap_or(Binding::Null)),
+                _ => Err(error("Expected generator for next builtin")),
             },
+            Builtin::Native(native) => native.call(args.to_vec(), &mut env.clone()),
             Builtin::Puts => {
                 for arg in args {
-                    stdout
+                    env.stdout
                         .borrow_mut()
-                        .write_fmt(format_args!("{}\n", arg))
+                        .write_fmt(format_args!("{}\n", text(arg)))
                         .unwrap();
                 }
                 Ok(Binding::Null)
@@ -107,6 +92,8 @@ pub enum Primitive {
     Int(i64),
     String_(String),
     Bool(bool),
+    //tuples of primitives, so they can be used as hash keys
+    Tuple(Vec<Primitive>),
 }
 
 impl Display for Primitive {
@@ -115,6 +102,7 @@ impl Display for Primitive {
             Primitive::Int(i) => write!(f, "{}", i),
             Primitive::Bool(b) => write!(f, "{}", b),
             Primitive::String_(s) => write!(f, "'{}'", s),
+            Primitive::Tuple(t) => write_tuple(f, t),
         }
     }
 }
@@ -130,15 +118,379 @@ pub enum Binding {
     //null
     Null,
     Builtin(Builtin),
+    //runtime errors, catchable like any thrown value
+    Error(String),
+    //calling a struct declaration constructs a record of it
+    Struct(Rc<Struct>),
+    Record(Rc<Struct>, Vec<Binding>),
+    //tuples holding non primitive values
+    Tuple(Vec<Binding>),
+    Generator(Rc<RefCell<Generator>>),
+}
+
+//Where a suspended generator body picks up again
+#[derive(Debug)]
+enum Cont {
+    //statements of a block, the next one to run and the block's env
+    Block(Vec<Expr>, usize, Rc<Env>),
+    //a try statement whose body is running above it
+    Try(Try, Rc<Env>),
+    //a generator passed to `yield*`
+    Delegate(Rc<RefCell<Generator>>),
+    //where the body of a generator moved in by `yield*` ends
+    Inlined,
+}
+
+//A generator body runs on an explicit continuation stack instead of the
+//Rust one, so it can stop at a yield and carry on from there later
+#[derive(Debug)]
+pub struct Generator {
+    conts: Vec<Cont>,
+    running: bool,
+}
+
+//Where running a generator body stopped
+enum Step {
+    Yield(Binding),
+    //a `yield*` to run until it is done before carrying on
+    Delegate(Rc<RefCell<Generator>>),
+    Done,
+}
+
+//Takes the body out of a generator to run it
+fn start(gen: &Rc<RefCell<Generator>>) -> Result<Vec<Cont>, Binding> {
+    let mut gen = gen.borrow_mut();
+    if gen.running {
+        return Err(error("Generator is already running"));
+    }
+    gen.running = true;
+    Ok(std::mem::take(&mut gen.conts))
+}
+
+fn stop(gen: &Rc<RefCell<Generator>>, conts: Vec<Cont>) {
+    let mut gen = gen.borrow_mut();
+    gen.running = false;
+    gen.conts = conts;
 }
+
+//Runs a generator until its next yield, giving `None` once the body is done.
+//Generators delegated to with `yield*` are kept in a chain, innermost last,
+//and resumed in a loop so long delegations don't grow the Rust stack
+fn resume(gen: &Rc<RefCell<Generator>>) -> Result<Option<Binding>, Binding> {
+    let mut chain = vec![(gen.clone(), start(gen)?)];
+    loop {
+        let (_, conts) = chain.last_mut().unwrap();
+        let mut outcome = match run_generator(conts) {
+            Ok(Step::Yield(value)) => {
+                for (gen, conts) in chain {
+                    stop(&gen, conts);
+                }
+                return Ok(Some(value));
+            }
+            Ok(Step::Delegate(inner)) => match start(&inner) {
+                Ok(inner_conts) => {
+                    chain.push((inner, inner_conts));
+                    continue;
+                }
+                Err(thrown) => match unwind(conts, thrown) {
+                    Ok(()) => continue,
+                    Err(thrown) => Err(thrown),
+                },
+            },
+            Ok(Step::Done) => Ok(()),
+            Err(thrown) => Err(thrown),
+        };
+        //the innermost generator is done or threw, its delegator carries on
+        loop {
+            let (gen, conts) = chain.pop().unwrap();
+            stop(&gen, conts);
+            let Some((_, conts)) = chain.last_mut() else {
+                return outcome.map(|()| None);
+            };
+            match outcome {
+                Ok(()) => {
+                    conts.pop();
+                    break;
+                }
+                Err(thrown) => match unwind(conts, thrown) {
+                    Ok(()) => break,
+                    Err(thrown) => outcome = Err(thrown),
+                },
+            }
+        }
+    }
+}
+
+fn run_generator(conts: &mut Vec<Cont>) -> Result<Step, Binding> {
+    while let Some(cont) = conts.last_mut() {
+        let (stmt, env) = match cont {
+            Cont::Block(stmts, ix, env) if *ix < stmts.len() => {
+                *ix += 1;
+                (stmts[*ix - 1].clone(), env.clone())
+            }
+            Cont::Delegate(gen) => return Ok(Step::Delegate(gen.clone())),
+            _ => {
+                conts.pop();
+                continue;
+            }
+        };
+        let env = &env;
+        let result = match stmt {
+            Expr::Yield(e) => match e.eval(env) {
+                Ok(Binding::Return(value)) => return Ok(Step::Yield(*value)),
+                Ok(value) => return Ok(Step::Yield(value)),
+                Err(thrown) => Err(thrown),
+            },
+            Expr::Delegate(e) => match e.eval(env) {
+                //nothing else can reach a generator only held here, so its
+                //body is moved on top instead of being resumed every step
+                Ok(Binding::Generator(gen)) => {
+                    match Rc::try_unwrap(gen) {
+                        Ok(gen) => inline(conts, gen.into_inner().conts),
+                        Err(gen) => conts.push(Cont::Delegate(gen)),
+                    }
+                    Ok(())
+                }
+                Ok(_) => Err(error("Expected generator for yield*")),
+                Err(thrown) => Err(thrown),
+            },
+            Expr::If(i) => i.condition.eval(env).map(|condition| {
+                let branch = if truthy(&condition) {
+                    Some(&i.consequence)
+                } else {
+                    i.alternative.as_ref()
+                };
+                if let Some(branch) = branch {
+                    conts.push(Cont::Block(branch.0.clone(), 0, Env::child(env)));
+                }
+            }),
+            Expr::Try(t) => {
+                let body = t.body.0.clone();
+                conts.push(Cont::Try(t, env.clone()));
+                conts.push(Cont::Block(body, 0, Env::child(env)));
+                Ok(())
+            }
+            stmt => stmt.eval(env).map(|value| {
+                //returning ends the generator, the value is dropped. An
+                //inlined body only ends itself
+                if matches!(value, Binding::Return(_)) {
+                    while let Some(cont) = conts.pop() {
+                        if matches!(cont, Cont::Inlined) {
+                            break;
+                        }
+                    }
+                }
+            }),
+        };
+        if let Err(thrown) = result {
+            unwind(conts, thrown)?;
+        }
+    }
+    Ok(Step::Done)
+}
+
+//Moves the body of a generator passed to `yield*` on top of `conts`
+fn inline(conts: &mut Vec<Cont>, body: Vec<Cont>) {
+    //blocks that ran to the end are dropped first so recursive delegations
+    //don't pile up
+    while matches!(conts.last(), Some(Cont::Block(stmts, ix, _)) if *ix >= stmts.len()) {
+        conts.pop();
+    }
+    if !matches!(conts.last(), None | Some(Cont::Inlined)) {
+        conts.push(Cont::Inlined);
+    }
+    conts.extend(body);
+}
+
+//Continues in the closest catch block, the thrown value is returned if
+//there is none
+fn unwind(conts: &mut Vec<Cont>, thrown: Binding) -> Result<(), Binding> {
+    loop {
+        match conts.pop() {
+            Some(Cont::Try(t, env)) => {
+                let env = Env::child(&env);
+                env.local.borrow_mut().insert(t.name.clone(), thrown);
+                conts.push(Cont::Block(t.handler.0, 0, env));
+                return Ok(());
+            }
+            Some(_) => continue,
+            None => return Err(thrown),
+        }
+    }
+}
+
+fn write_tuple<T: Display>(f: &mut std::fmt::Formatter<'_>, elems: &[T]) -> std::fmt::Result {
+    match elems {
+        [elem] => write!(f, "({},)", elem),
+        _ => write!(
+            f,
+            "({})",
+            elems
+                .iter()
+                .map(|e| format!("{}", e))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+//Builds a tuple, using the primitive form whenever every element is a
+//primitive so equal tuples always hash the same
+fn tuple(elems: Vec<Binding>) -> Binding {
+    if elems.iter().all(|e| matches!(e, Binding::Primitive(_))) {
+        Binding::Primitive(Tuple(
+            elems
+                .into_iter()
+                .map(|e| match e {
+                    Binding::Primitive(p) => p,
+                    _ => unreachable!(),
+                })
+                .collect(),
+        ))
+    } else {
+        Binding::Tuple(elems)
+    }
+}
+
+impl Binding {
+    fn tuple_elems(self) -> Option<Vec<Binding>> {
+        match self {
+            Binding::Tuple(t) => Some(t),
+            Binding::Primitive(Tuple(t)) => Some(t.into_iter().map(Binding::Primitive).collect()),
+            _ => None,
+        }
+    }
+}
+
+impl Value for Binding {
+    type Key = Primitive;
+    fn null() -> Self {
+        Binding::Null
+    }
+    fn int(i: i64) -> Self {
+        Binding::Primitive(Int(i))
+    }
+    fn bool(b: bool) -> Self {
+        Binding::Primitive(Bool(b))
+    }
+    fn string(s: String) -> Self {
+        Binding::Primitive(String_(s))
+    }
+    fn array(elems: Vec<Self>) -> Self {
+        Binding::Array(elems)
+    }
+    fn tuple(elems: Vec<Self>) -> Self {
+        tuple(elems)
+    }
+    fn hash(entries: HashMap<Primitive, Self>) -> Self {
+        Binding::Hash(entries)
+    }
+    fn error(msg: &str) -> Self {
+        error(msg)
+    }
+    fn is_null(&self) -> bool {
+        matches!(self, Binding::Null)
+    }
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Binding::Primitive(Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Binding::Primitive(Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Binding::Primitive(String_(s)) => Some(s),
+            _ => None,
+        }
+    }
+    fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Binding::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+    fn as_tuple(&self) -> Option<Vec<Self>> {
+        self.clone().tuple_elems()
+    }
+    fn as_hash(&self) -> Option<&HashMap<Primitive, Self>> {
+        match self {
+            Binding::Hash(h) => Some(h),
+            _ => None,
+        }
+    }
+    fn as_record(&self) -> Option<(&str, &[Self])> {
+        match self {
+            Binding::Record(s, fields) => Some((&s.name, fields)),
+            _ => None,
+        }
+    }
+    fn to_key(&self) -> Option<Primitive> {
+        match self {
+            Binding::Primitive(p) => Some(p.clone()),
+            _ => None,
+        }
+    }
+    fn from_key(key: Primitive) -> Self {
+        Binding::Primitive(key)
+    }
+    fn type_of(&self) -> String {
+        match self {
+            Binding::Primitive(Int(_)) => "int",
+            Binding::Primitive(String_(_)) => "string",
+            Binding::Primitive(Bool(_)) => "bool",
+            Binding::Primitive(Primitive::Tuple(_)) | Binding::Tuple(_) => "tuple",
+            Binding::Null => "null",
+            Binding::Array(_) => "array",
+            Binding::Hash(_) => "hash",
+            Binding::Fn(..) => "function",
+            Binding::Builtin(_) => "builtin",
+            Binding::Generator(_) => "generator",
+            Binding::Struct(_) => "struct",
+            Binding::Record(s, _) => return s.name.clone(),
+            Binding::Error(_) => "error",
+            Binding::Return(value) => return value.type_of(),
+        }
+        .to_string()
+    }
+    fn arity(&self) -> Option<i64> {
+        match self {
+            Binding::Fn(_, f) if f.rest.is_some() => Some(-1),
+            Binding::Fn(_, f) => Some(f.args.len() as i64),
+            Binding::Builtin(builtin) => Some(builtin.arity()),
+            Binding::Struct(s) => Some(s.fields.len() as i64),
+            _ => None,
+        }
+    }
+}
+
+fn error(msg: &str) -> Binding {
+    Binding::Error(msg.to_string())
+}
+//Anything thrown, including runtime errors, comes back as the `Err` binding
 pub trait Evaluation {
-    fn eval(&self, env: &Rc<Env>) -> Result<Binding, &'static str>;
+    fn eval(&self, env: &Rc<Env>) -> Result<Binding, Binding>;
 }
 #[derive(Debug)]
 pub struct Env {
     pub stdout: Rc<RefCell<String>>,
     pub local: RefCell<HashMap<String, Binding>>,
     pub enclosing: Option<Rc<Env>>,
+    //names in `local` bound with const, kept so later programs run in the
+    //same env can't redefine them either
+    pub constants: RefCell<HashSet<String>>,
+    //top level names of the programs run in this env, like the globals the
+    //vm declares up front, so reads before their let can say so
+    pub declared: RefCell<HashSet<String>>,
+    pub clock: Rc<dyn Clock>,
+    pub rng: Rc<RefCell<Rng>>,
+    //calls running that aren't tail calls, shared by every env of a run
+    pub depth: Rc<Cell<usize>>,
 }
 
 impl Env {
@@ -148,8 +500,55 @@ impl Env {
             local: RefCell::new(HashMap::new()),
             enclosing,
             stdout: Rc::new(RefCell::new(String::new())),
+            constants: RefCell::new(HashSet::new()),
+            declared: RefCell::new(HashSet::new()),
+            clock: Rc::new(SystemClock),
+            rng: Rc::default(),
+            depth: Rc::default(),
         }
     }
+    //Seeds the generator random builtins draw from
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rc::new(RefCell::new(Rng::new(seed)));
+        self
+    }
+    //Replaces the clock `now` reads, a fixed one keeps output the same
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    //A fresh scope inside `parent`, for a block or a call
+    pub fn child(parent: &Rc<Env>) -> Rc<Env> {
+        Rc::new(Self {
+            local: RefCell::new(HashMap::new()),
+            enclosing: Some(parent.clone()),
+            stdout: parent.stdout.clone(),
+            constants: RefCell::new(HashSet::new()),
+            declared: RefCell::new(HashSet::new()),
+            clock: parent.clock.clone(),
+            rng: parent.rng.clone(),
+            depth: parent.depth.clone(),
+        })
+    }
+    //Defines the prelude helpers, meant for a root env before any user code.
+    //They are evaluated in an env of their own, so like in the vm they keep
+    //calling the builtins when user code defines the same names
+    pub fn load_prelude(self: &Rc<Self>) {
+        let prelude_env = Rc::new(Self {
+            local: RefCell::new(HashMap::new()),
+            enclosing: None,
+            stdout: self.stdout.clone(),
+            constants: RefCell::new(HashSet::new()),
+            declared: RefCell::new(HashSet::new()),
+            clock: self.clock.clone(),
+            rng: self.rng.clone(),
+            depth: self.depth.clone(),
+        });
+        prelude::program()
+            .eval(&prelude_env)
+            .expect("Prelude should evaluate");
+        self.local.borrow_mut().extend(prelude_env.local.take());
+    }
     pub fn get(&self, name: &str) -> Option<Binding> {
         if let Some(val) = self.local.borrow().get(name) {
             return Some(val.clone());
@@ -160,23 +559,74 @@ impl Env {
             None
         }
     }
+    //The error for reading `name` when no env defines it
+    fn undefined(&self, name: &str) -> Binding {
+        if self.declared.borrow().contains(name) {
+            return error(&format!("{} is used before it is defined", name));
+        }
+        match self.enclosing.as_ref() {
+            Some(enclosing) => enclosing.undefined(name),
+            None => error(&format!("Undefined variable {}", name)),
+        }
+    }
+    //Rebinds an existing name in the env that defines it
+    pub fn set(&self, name: &str, value: Binding) -> bool {
+        if let Some(val) = self.local.borrow_mut().get_mut(name) {
+            *val = value;
+            return true;
+        }
+        if let Some(enclosing) = self.enclosing.as_ref() {
+            enclosing.set(name, value)
+        } else {
+            false
+        }
+    }
 }
 impl Default for Env {
     fn default() -> Self {
         Self::new(None)
     }
 }
+//Natives call functions from the env of the call to them
+impl Context<Binding> for Rc<Env> {
+    fn apply(&mut self, f: Binding, args: Vec<Binding>) -> Result<Binding, Binding> {
+        call(f, args, self)
+    }
+    fn print(&mut self, text: &str) {
+        self.stdout.borrow_mut().push_str(text);
+    }
+    fn now(&mut self) -> i64 {
+        self.clock.now()
+    }
+    fn rng<T>(&mut self, f: impl FnOnce(&mut Rng) -> T) -> T {
+        f(&mut self.rng.borrow_mut())
+    }
+}
 
 impl Display for Binding {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Binding::Primitive(Primitive::Int(i)) => write!(f, "{}", i),
-            Binding::Primitive(Primitive::Bool(b)) => write!(f, "{}", b),
-            Binding::Primitive(Primitive::String_(s)) => write!(f, "'{}'", s),
+            Binding::Primitive(p) => write!(f, "{}", p),
+            Binding::Tuple(t) => write_tuple(f, t),
             Binding::Null => write!(f, "null"),
+            Binding::Generator(_) => write!(f, "generator"),
+            Binding::Error(e) => write!(f, "error: {}", e),
+            Binding::Struct(s) => write!(f, "{}", s),
+            Binding::Record(s, fields) => write!(
+                f,
+                "{} {{ {} }}",
+                s.name,
+                s.fields
+                    .iter()
+                    .zip(fields)
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
             Binding::Return(r) => write!(f, "{}", r),
-            Binding::Builtin(b) => write!(f, "{}", b),
-            Binding::Fn(_, fn_) => write!(f, "fn({}) {{ {} }}", fn_.args.join(", "), fn_.body),
+            Binding::Builtin(b) => write!(f, "builtin {}", b),
+            Binding::Fn(_, fn_) if fn_.generator => write!(f, "gen fn/{}", fn_.args.len()),
+            Binding::Fn(_, fn_) => write!(f, "fn/{}", fn_.args.len()),
             Binding::Array(a) => write!(
                 f,
                 "[{}]",
@@ -185,10 +635,12 @@ impl Display for Binding {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            Binding::Hash(h) if h.is_empty() => write!(f, "{{}}"),
             Binding::Hash(h) => write!(
                 f,
                 "{{ {} }}",
-                h.iter()
+                sorted_entries(h)
+                    .iter()
                     .map(|(k, v)| format!("{}: {}", k, v))
                     .collect::<Vec<String>>()
                     .join(", ")
@@ -198,19 +650,31 @@ impl Display for Binding {
 }
 
 impl Evaluation for Expr {
-    fn eval(&self, env: &Rc<Env>) -> Result<Binding, &'static str> {
+    fn eval(&self, env: &Rc<Env>) -> Result<Binding, Binding> {
         match self {
             Expr::Int(i) => Ok(Binding::Primitive(Int(*i))),
             Expr::Bool(b) => Ok(Binding::Primitive(Bool(*b))),
             Expr::String(s) => Ok(Binding::Primitive(String_(s.clone()))),
+            Expr::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    let part = part.eval(env)?;
+                    let part = if let Binding::Return(p) = part {
+                        *p
+                    } else {
+                        part
+                    };
+                    string.push_str(&text(&part));
+                }
+                Ok(Binding::Primitive(String_(string)))
+            }
             Expr::Identifier(i) => {
                 if let Some(binding) = env.get(i) {
                     Ok(binding.clone())
                 } else if let Ok(builtin) = Builtin::from_str(i) {
                     Ok(Binding::Builtin(builtin))
                 } else {
-                    println!("{:?}", env.local);
-                    Err("Undefined variable")
+                    Err(env.undefined(i))
                 }
             }
             Expr::Prefix(p) => match p.token {
@@ -224,7 +688,7 @@ impl Evaluation for Expr {
                     match operand {
                         Binding::Primitive(Int(i)) => Ok(Binding::Primitive(Bool(i == 0))),
                         Binding::Primitive(Bool(b)) => Ok(Binding::Primitive(Bool(!b))),
-                        _ => Err("Expected int or bool for ! operator"),
+                        _ => Err(error("Expected int or bool for ! operator")),
                     }
                 }
                 Token::Minus => {
@@ -236,10 +700,10 @@ impl Evaluation for Expr {
                     };
                     match operand {
                         Binding::Primitive(Int(i)) => Ok(Binding::Primitive(Int(-i))),
-                        _ => Err("Expected int for - operator"),
+                        _ => Err(error("Expected int for - operator")),
                     }
                 }
-                _ => Err("Expected bang"),
+                _ => Err(error("Expected bang")),
             },
             Expr::Infix(i) => {
                 let left = i.left.eval(env)?;
@@ -259,109 +723,231 @@ impl Evaluation for Expr {
                         Token::Plus => Ok(Binding::Primitive(Int(l + r))),
                         Token::Minus => Ok(Binding::Primitive(Int(l - r))),
                         Token::Star => Ok(Binding::Primitive(Int(l * r))),
+                        Token::Slash | Token::Percent if r == 0 => Err(error("Division by zero")),
                         Token::Slash => Ok(Binding::Primitive(Int(l / r))),
                         Token::Eq => Ok(Binding::Primitive(Bool(l == r))),
                         Token::Neq => Ok(Binding::Primitive(Bool(l != r))),
                         Token::Lt => Ok(Binding::Primitive(Bool(l < r))),
                         Token::Gt => Ok(Binding::Primitive(Bool(l > r))),
                         Token::Percent => Ok(Binding::Primitive(Int(l % r))),
-                        _ => Err("invald infix operator for int"),
+                        _ => Err(error("Invalid op for ints")),
                     },
                     (Binding::Primitive(Bool(l)), Binding::Primitive(Bool(r))) => match i.token {
                         Token::Eq => Ok(Binding::Primitive(Bool(l == r))),
                         Token::Neq => Ok(Binding::Primitive(Bool(l != r))),
-                        _ => Err("invald infix operator for bool"),
+                        _ => Err(error("Invalid op for bools")),
                     },
                     (Binding::Primitive(String_(l)), Binding::Primitive(String_(r))) => {
                         match i.token {
                             Token::Eq => Ok(Binding::Primitive(Bool(l == r))),
                             Token::Neq => Ok(Binding::Primitive(Bool(l != r))),
                             Token::Plus => Ok(Binding::Primitive(String_(format!("{}{}", l, r)))),
-                            _ => Err("invald infix operator for string"),
+                            _ => Err(error("Invalid op for string")),
                         }
                     }
                     (Binding::Array(l), Binding::Primitive(Int(r))) => match i.token {
+                        Token::LBracket => match normalize_index(r, l.len()) {
+                            Some(ix) => Ok(l[ix].clone()),
+                            None => Err(error("Index out of bounds for array")),
+                        },
+                        _ => Err(error("Invalid op for array")),
+                    },
+                    (Binding::Primitive(String_(l)), Binding::Primitive(Int(r))) => match i.token {
                         Token::LBracket => {
-                            if r < 0 {
-                                Err("Expected positive index for array")
-                            } else if r as usize >= l.len() {
-                                Err("Expected index less than array length")
-                            } else {
-                                Ok(l[r as usize].clone())
+                            let chars = l.chars().collect::<Vec<_>>();
+                            match normalize_index(r, chars.len()) {
+                                Some(ix) => Ok(Binding::Primitive(String_(chars[ix].into()))),
+                                None => Err(error("Index out of bounds for string")),
                             }
                         }
-                        _ => Err("Invalid infix operator for array"),
+                        _ => Err(error("Invalid op for string")),
                     },
-                    (Binding::Hash(l), Binding::Primitive(key)) => {
-                        if let Some(key) = l.get(&key) {
-                            Ok(key.clone())
-                        } else {
-                            Err("Invalid key for hash")
+                    (
+                        l @ (Binding::Tuple(_) | Binding::Primitive(Tuple(_))),
+                        Binding::Primitive(Int(r)),
+                    ) => {
+                        let elems = l.tuple_elems().unwrap();
+                        match i.token {
+                            Token::LBracket => match normalize_index(r, elems.len()) {
+                                Some(ix) => Ok(elems[ix].clone()),
+                                None => Err(error("Index out of bounds for tuple")),
+                            },
+                            _ => Err(error("Invalid op for tuple")),
                         }
                     }
-                    _ => Err("invalid infix operator and types"),
+                    (Binding::Hash(l), Binding::Primitive(key)) => match i.token {
+                        Token::LBracket => {
+                            if let Some(key) = l.get(&key) {
+                                Ok(key.clone())
+                            } else {
+                                Err(error("Invalid key for hash"))
+                            }
+                        }
+                        _ => Err(error("Invalid op for hash")),
+                    },
+                    //records are equal when they are of the same struct and
+                    //their fields are equal
+                    (l @ Binding::Record(..), r @ Binding::Record(..)) => match i.token {
+                        Token::Eq => Ok(Binding::Primitive(Bool(equal(&l, &r)))),
+                        Token::Neq => Ok(Binding::Primitive(Bool(!equal(&l, &r)))),
+                        _ => Err(error("Expected == or != to compare records")),
+                    },
+                    _ => Err(error("Invalid types for binary op")),
+                }
+            }
+            Expr::Slice(sl) => {
+                let left = sl.left.eval(env)?;
+                let left = if let Binding::Return(l) = left {
+                    *l
+                } else {
+                    left
+                };
+                let bound = |bound: &Option<Expr>| match bound {
+                    Some(bound) => match bound.eval(env)? {
+                        Binding::Primitive(Int(i)) => Ok(Some(i)),
+                        Binding::Null => Ok(None),
+                        _ => Err(error("Expected int bounds for slice")),
+                    },
+                    None => Ok(None),
+                };
+                let (start, end) = (bound(&sl.start)?, bound(&sl.end)?);
+                match left {
+                    Binding::Array(a) => {
+                        let (start, end) = slice_bounds(start, end, a.len());
+                        Ok(Binding::Array(a[start..end].to_vec()))
+                    }
+                    Binding::Primitive(String_(s)) => {
+                        let chars = s.chars().collect::<Vec<_>>();
+                        let (start, end) = slice_bounds(start, end, chars.len());
+                        Ok(Binding::Primitive(String_(
+                            chars[start..end].iter().collect(),
+                        )))
+                    }
+                    _ => Err(error("Expected array or string for slice")),
                 }
             }
             Expr::If(i) => {
                 let condition = i.condition.eval(env)?;
-                match condition {
-                    Binding::Primitive(Bool(true)) | Binding::Primitive(Int(_)) => {
-                        i.consequence.eval(env)
-                    }
-                    _ => match &i.alternative {
+                if truthy(&condition) {
+                    i.consequence.eval(env)
+                } else {
+                    match &i.alternative {
                         Some(alternative) => alternative.eval(env),
                         None => Ok(Binding::Null),
-                    },
+                    }
                 }
             }
             Expr::Fn(f) => Ok(Binding::Fn(env.clone(), f.clone())),
             Expr::Call(c) => {
                 let fn_ = c.expr.eval(env)?;
-                let mut args = Vec::new();
-                for arg in &c.args {
-                    args.push(arg.eval(env)?);
-                }
-                if let Binding::Fn(fn_env, fn_) = fn_ {
-                    let fn_env = Env {
-                        local: fn_env.local.clone(),
-                        enclosing: Some(env.clone()),
-                        stdout: fn_env.stdout.clone(),
-                    };
-                    zip(fn_.args.clone(), args).for_each(|(name, arg)| {
-                        fn_env.local.borrow_mut().insert(name, arg);
-                    });
-                    fn_.body.eval(&Rc::new(fn_env))
-                } else if let Binding::Builtin(builtin) = fn_ {
-                    builtin.eval(args.as_slice(), &env.stdout)
-                } else {
-                    Err("Expected function literal or identifier")
+                call(fn_, elements(&c.args, env)?, env)
+            }
+            Expr::Try(t) => match t.body.eval(env) {
+                Err(thrown) => {
+                    let env = Env::child(env);
+                    env.local.borrow_mut().insert(t.name.clone(), thrown);
+                    t.handler.0.eval(&env)
                 }
+                value => value,
+            },
+            Expr::Yield(_) | Expr::Delegate(_) => Err(error("yield outside of a generator body")),
+            Expr::Throw(e) => {
+                let thrown = e.eval(env)?;
+                let thrown = if let Binding::Return(t) = thrown {
+                    *t
+                } else {
+                    thrown
+                };
+                Err(thrown)
             }
             Expr::Return(e) => {
                 let boxed = Box::new(e.eval(env)?);
                 Ok(Binding::Return(boxed))
             }
+            Expr::Struct(s) => {
+                let value = Binding::Struct(s.clone());
+                env.local.borrow_mut().insert(s.name.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::Field(field) => {
+                let left = field.left.eval(env)?;
+                let left = if let Binding::Return(l) = left {
+                    *l
+                } else {
+                    left
+                };
+                match left {
+                    Binding::Record(s, fields) => {
+                        match s.fields.iter().position(|f| *f == field.name) {
+                            Some(ix) => Ok(fields[ix].clone()),
+                            None => Err(error("Unknown field for record")),
+                        }
+                    }
+                    _ => Err(error("Expected record for field access")),
+                }
+            }
+            Expr::Assign(a) => {
+                let value = a.value.eval(env)?;
+                let value = if let Binding::Return(v) = value {
+                    *v
+                } else {
+                    value
+                };
+                assign(&a.target, value.clone(), env)?;
+                Ok(value)
+            }
             Expr::Let(l) => {
                 let value = l.value.eval(env)?;
                 println!("Let binding {} {}", l.name, value);
+                if l.constant {
+                    env.constants.borrow_mut().insert(l.name.clone());
+                }
                 env.local.borrow_mut().insert(l.name.clone(), value.clone());
                 Ok(value)
             }
-            Expr::Array(a) => {
-                let mut array = Vec::new();
-                for elem in a {
-                    array.push(elem.eval(env)?);
+            Expr::Tuple(t) => {
+                let mut elems = Vec::new();
+                for elem in t {
+                    elems.push(elem.eval(env)?);
                 }
-                Ok(Binding::Array(array))
+                Ok(tuple(elems))
             }
+            Expr::Destructure(d) => {
+                let value = d.value.eval(env)?;
+                let value = if let Binding::Return(v) = value {
+                    *v
+                } else {
+                    value
+                };
+                let elems = match value.clone().tuple_elems() {
+                    Some(elems) if elems.len() == d.names.len() => elems,
+                    Some(_) => return Err(error("Wrong number of names to destructure tuple")),
+                    None => return Err(error("Expected tuple to destructure")),
+                };
+                for (name, elem) in d.names.iter().zip(elems) {
+                    env.local.borrow_mut().insert(name.clone(), elem);
+                }
+                Ok(value)
+            }
+            Expr::Array(a) => Ok(Binding::Array(elements(a, env)?)),
+            Expr::Spread(_) => Err(error("Spread outside of an array or call")),
             //Expr::Block(b) => b.eval(env),
             Expr::Hash(h) => {
                 let mut hash = HashMap::new();
-                for (key, value) in h {
-                    let key = key.eval(env)?;
-                    let value = value.eval(env)?;
-                    if let Binding::Primitive(key) = key {
-                        hash.insert(key, value);
+                for entry in h {
+                    match entry {
+                        HashEntry::Pair(key, value) => {
+                            let key = key.eval(env)?;
+                            let value = value.eval(env)?;
+                            match key.to_key() {
+                                Some(key) => hash.insert(key, value),
+                                None => return Err(error("Invalid key for hash")),
+                            };
+                        }
+                        HashEntry::Spread(spread) => match spread.eval(env)? {
+                            Binding::Hash(entries) => hash.extend(entries),
+                            _ => return Err(error("Expected hash to spread")),
+                        },
                     }
                 }
                 Ok(Binding::Hash(hash))
@@ -370,8 +956,206 @@ impl Evaluation for Expr {
     }
 }
 
+//Evaluates array elements or call arguments, spreading arrays and tuples
+fn elements(exprs: &[Expr], env: &Rc<Env>) -> Result<Vec<Binding>, Binding> {
+    let mut elems = Vec::new();
+    for expr in exprs {
+        match expr {
+            Expr::Spread(spread) => {
+                let spread = spread.eval(env)?;
+                match spread {
+                    Binding::Array(a) => elems.extend(a),
+                    spread => match spread.tuple_elems() {
+                        Some(t) => elems.extend(t),
+                        None => return Err(error("Expected array or tuple to spread")),
+                    },
+                }
+            }
+            expr => elems.push(expr.eval(env)?),
+        }
+    }
+    Ok(elems)
+}
+
+fn truthy(condition: &Binding) -> bool {
+    matches!(
+        condition,
+        Binding::Primitive(Bool(true)) | Binding::Primitive(Int(_))
+    )
+}
+
+//What a function body ends with, calls in tail position are handed back to
+//the call loop instead of being made so deep recursion doesn't grow the stack
+enum Tail {
+    Value(Binding),
+    Call(Binding, Vec<Binding>),
+}
+
+//Each call that isn't a tail call recurses on the host stack, a few KB of
+//it in release builds, so this keeps well inside the 1MB wasm stack
+const MAX_CALL_DEPTH: usize = 200;
+
+//Calls `fn_`, throwing like the vm when calls nest too deep
+fn call(fn_: Binding, args: Vec<Binding>, env: &Rc<Env>) -> Result<Binding, Binding> {
+    let depth = env.depth.get();
+    if depth == MAX_CALL_DEPTH {
+        return Err(error("Stack overflow"));
+    }
+    env.depth.set(depth + 1);
+    let result = tail_calls(fn_, args, env);
+    env.depth.set(depth);
+    result
+}
+
+//Runs `fn_`, and the tail calls in its body in the same loop
+fn tail_calls(mut fn_: Binding, mut args: Vec<Binding>, env: &Rc<Env>) -> Result<Binding, Binding> {
+    loop {
+        match fn_ {
+            Binding::Fn(closure, f) => {
+                let fn_env = Env::child(&closure);
+                //a named function can call itself even where the name isn't
+                //bound, the args still shadow it
+                if let Some(name) = &f.name {
+                    let self_ = Binding::Fn(closure.clone(), f.clone());
+                    fn_env.local.borrow_mut().insert(name.clone(), self_);
+                }
+                bind_args(&f, args, &fn_env)?;
+                if f.generator {
+                    return Ok(Binding::Generator(Rc::new(RefCell::new(Generator {
+                        conts: vec![Cont::Block(f.body.0.clone(), 0, fn_env)],
+                        running: false,
+                    }))));
+                }
+                match tail_block(&f.body.0, &fn_env, true)? {
+                    Some(Tail::Call(next, next_args)) => (fn_, args) = (next, next_args),
+                    Some(Tail::Value(value)) => return Ok(value),
+                    None => unreachable!(),
+                }
+            }
+            Binding::Builtin(builtin) => return builtin.eval(args.as_slice(), env),
+            Binding::Struct(s) => {
+                if args.len() != s.fields.len() {
+                    return Err(error("Wrong number of fields for struct constructor"));
+                }
+                return Ok(Binding::Record(s, args));
+            }
+            _ => return Err(error("Expected closure or builtin for call")),
+        }
+    }
+}
+
+//Runs the statements of a function body, or of a block in it, until one
+//returns. `last` is set when the block's value is the function's, otherwise
+//`None` means it finished without returning
+fn tail_block(stmts: &[Expr], env: &Rc<Env>, last: bool) -> Result<Option<Tail>, Binding> {
+    for (ix, stmt) in stmts.iter().enumerate() {
+        let is_last = last && ix + 1 == stmts.len();
+        match stmt {
+            Expr::Return(e) => return eval_tail(e, env).map(Some),
+            Expr::If(i) => {
+                let condition = i.condition.eval(env)?;
+                let branch = if truthy(&condition) {
+                    Some(&i.consequence)
+                } else {
+                    i.alternative.as_ref()
+                };
+                match branch {
+                    Some(branch) => {
+                        if let Some(tail) = tail_block(&branch.0, &Env::child(env), is_last)? {
+                            return Ok(Some(tail));
+                        }
+                    }
+                    None if is_last => return Ok(Some(Tail::Value(Binding::Null))),
+                    None => {}
+                }
+            }
+            _ if is_last => return eval_tail(stmt, env).map(Some),
+            _ => {
+                if let Binding::Return(value) = stmt.eval(env)? {
+                    return Ok(Some(Tail::Value(*value)));
+                }
+            }
+        }
+    }
+    //only reached by empty blocks when `last` is set
+    Ok(last.then_some(Tail::Value(Binding::Null)))
+}
+
+fn eval_tail(expr: &Expr, env: &Rc<Env>) -> Result<Tail, Binding> {
+    match expr {
+        Expr::Call(c) => {
+            let fn_ = c.expr.eval(env)?;
+            Ok(Tail::Call(fn_, elements(&c.args, env)?))
+        }
+        Expr::If(_) => Ok(tail_block(std::slice::from_ref(expr), env, true)?.unwrap()),
+        _ => match expr.eval(env)? {
+            Binding::Return(value) => Ok(Tail::Value(*value)),
+            value => Ok(Tail::Value(value)),
+        },
+    }
+}
+
+//Stores `value` in an assignment target, updating every record on the way
+//back to the variable that holds it
+fn assign(target: &Expr, value: Binding, env: &Rc<Env>) -> Result<(), Binding> {
+    match target {
+        Expr::Identifier(name) => {
+            if env.set(name, value) {
+                Ok(())
+            } else {
+                Err(env.undefined(name))
+            }
+        }
+        Expr::Field(field) => match field.left.eval(env)? {
+            Binding::Record(s, mut fields) => {
+                match s.fields.iter().position(|f| *f == field.name) {
+                    Some(ix) => {
+                        fields[ix] = value;
+                        assign(&field.left, Binding::Record(s, fields), env)
+                    }
+                    None => Err(error("Unknown field for record")),
+                }
+            }
+            _ => Err(error("Expected record for field update")),
+        },
+        _ => Err(error("Invalid assignment target")),
+    }
+}
+
+//Binds call arguments to the parameters of `fn_`, filling in defaults and
+//collecting the extra arguments into the rest parameter
+fn bind_args(fn_: &Fn, mut args: Vec<Binding>, env: &Rc<Env>) -> Result<(), Binding> {
+    if args.len() < fn_.num_required() || (fn_.rest.is_none() && args.len() > fn_.args.len()) {
+        return Err(error(&format!(
+            "Expected {} args for call but got {}",
+            fn_.args.len(),
+            args.len()
+        )));
+    }
+    let extra = if args.len() > fn_.args.len() {
+        args.split_off(fn_.args.len())
+    } else {
+        Vec::new()
+    };
+    let mut args = args.into_iter();
+    for arg in &fn_.args {
+        let value = match (args.next(), &arg.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.eval(env)?,
+            (None, None) => unreachable!(),
+        };
+        env.local.borrow_mut().insert(arg.name.clone(), value);
+    }
+    if let Some(rest) = &fn_.rest {
+        env.local
+            .borrow_mut()
+            .insert(rest.clone(), Binding::Array(extra));
+    }
+    Ok(())
+}
+
 impl Evaluation for Vec<Expr> {
-    fn eval(&self, env: &Rc<Env>) -> Result<Binding, &'static str> {
+    fn eval(&self, env: &Rc<Env>) -> Result<Binding, Binding> {
         let mut bind = Binding::Null;
         for s in self {
             bind = s.eval(env)?;
@@ -382,14 +1166,163 @@ impl Evaluation for Vec<Expr> {
         Ok(bind)
     }
 }
+//Names defined in a block are only visible inside it
 impl Evaluation for Block {
-    fn eval(&self, env: &Rc<Env>) -> Result<Binding, &'static str> {
-        self.0.eval(env)
+    fn eval(&self, env: &Rc<Env>) -> Result<Binding, Binding> {
+        self.0.eval(&Env::child(env))
     }
 }
 
 impl Evaluation for Program {
-    fn eval(&self, env: &Rc<Env>) -> Result<Binding, &'static str> {
+    fn eval(&self, env: &Rc<Env>) -> Result<Binding, Binding> {
+        let globals = env
+            .local
+            .borrow()
+            .keys()
+            .map(|name| (name.clone(), env.constants.borrow().contains(name)))
+            .collect();
+        let mut check = ConstCheck {
+            scopes: vec![globals],
+            fns: Vec::new(),
+        };
+        check.statements(&self.statements)?;
+        env.declared
+            .borrow_mut()
+            .extend(self.statements.iter().flat_map(|s| match s {
+                Expr::Let(l) => vec![l.name.clone()],
+                Expr::Destructure(d) => d.names.clone(),
+                Expr::Struct(s) => vec![s.name.clone()],
+                _ => Vec::new(),
+            }));
         self.statements.eval(env)
     }
 }
+
+//Finds redefined and assigned consts, and assignments to names the VM can't
+//store into, before the program runs, following the scopes the evaluator
+//will create
+struct ConstCheck {
+    //names defined in each scope, and whether they're const
+    scopes: Vec<HashMap<String, bool>>,
+    //index of the scope holding the name of each function being checked,
+    //its parameters are in the scope after it
+    fns: Vec<usize>,
+}
+
+impl ConstCheck {
+    fn define(&mut self, name: &str, constant: bool) -> Result<(), Binding> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.get(name) == Some(&true) {
+            return Err(error(&format!(
+                "{} is a constant and can't be defined again",
+                name
+            )));
+        }
+        scope.insert(name.to_string(), constant);
+        Ok(())
+    }
+    fn scope(&mut self, names: &[&String], stmts: &[Expr]) -> Result<(), Binding> {
+        self.scopes
+            .push(names.iter().map(|name| (name.to_string(), false)).collect());
+        let result = self.statements(stmts);
+        self.scopes.pop();
+        result
+    }
+    fn statements(&mut self, stmts: &[Expr]) -> Result<(), Binding> {
+        stmts.iter().try_for_each(|s| self.expr(s))
+    }
+    fn exprs<'a>(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) -> Result<(), Binding> {
+        exprs.into_iter().try_for_each(|e| self.expr(e))
+    }
+    fn expr(&mut self, expr: &Expr) -> Result<(), Binding> {
+        match expr {
+            Expr::Int(_) | Expr::Bool(_) | Expr::String(_) | Expr::Identifier(_) => Ok(()),
+            Expr::Array(elems) | Expr::Tuple(elems) | Expr::Interpolation(elems) => {
+                self.exprs(elems)
+            }
+            Expr::Hash(entries) => entries.iter().try_for_each(|entry| match entry {
+                HashEntry::Pair(k, v) => self.exprs([k, v]),
+                HashEntry::Spread(h) => self.expr(h),
+            }),
+            Expr::Spread(e) => self.expr(e),
+            Expr::Prefix(p) => self.expr(&p.right),
+            Expr::Infix(i) => self.exprs([i.left.as_ref(), &i.right]),
+            Expr::Slice(s) => self.exprs(std::iter::once(&s.left).chain(&s.start).chain(&s.end)),
+            Expr::If(i) => {
+                self.expr(&i.condition)?;
+                self.scope(&[], &i.consequence.0)?;
+                match &i.alternative {
+                    Some(alternative) => self.scope(&[], &alternative.0),
+                    None => Ok(()),
+                }
+            }
+            Expr::Fn(f) => {
+                let mut names = f.args.iter().map(|a| &a.name).collect::<Vec<_>>();
+                names.extend(&f.rest);
+                self.exprs(f.args.iter().filter_map(|a| a.default.as_ref()))?;
+                self.fns.push(self.scopes.len());
+                self.scopes
+                    .push(f.name.iter().map(|name| (name.clone(), false)).collect());
+                let result = self.scope(&names, &f.body.0);
+                self.scopes.pop();
+                self.fns.pop();
+                result
+            }
+            Expr::Call(c) => self.exprs(std::iter::once(c.expr.as_ref()).chain(&c.args)),
+            Expr::Let(l) => {
+                self.expr(&l.value)?;
+                self.define(&l.name, l.constant)
+            }
+            Expr::Destructure(d) => {
+                self.expr(&d.value)?;
+                d.names.iter().try_for_each(|name| self.define(name, false))
+            }
+            Expr::Struct(s) => self.define(&s.name, false),
+            Expr::Try(t) => {
+                self.scope(&[], &t.body.0)?;
+                self.scope(&[&t.name], &t.handler.0)
+            }
+            Expr::Return(e) | Expr::Throw(e) | Expr::Yield(e) | Expr::Delegate(e) => self.expr(e),
+            Expr::Field(f) => self.expr(&f.left),
+            Expr::Assign(a) => {
+                self.expr(&a.value)?;
+                let mut target = &a.target;
+                while let Expr::Field(f) = target {
+                    target = &f.left;
+                }
+                match target {
+                    Expr::Identifier(name) => self.assignable(name),
+                    _ => Err(error("Invalid assignment target")),
+                }
+            }
+        }
+    }
+    //Only variables of the running function or the program can be assigned,
+    //not captured ones, the function's own name or builtins
+    fn assignable(&self, name: &str) -> Result<(), Binding> {
+        let found = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(ix, scope)| scope.get(name).map(|constant| (ix, *constant)));
+        match found {
+            Some((_, true)) => Err(error(&format!(
+                "{} is a constant and can't be assigned",
+                name
+            ))),
+            Some((ix, false)) => match (self.fns.first(), self.fns.last()) {
+                (Some(&outer), Some(&inner)) if ix >= outer && ix <= inner => Err(error(&format!(
+                    "{} is defined outside this function and can't be assigned",
+                    name
+                ))),
+                _ => Ok(()),
+            },
+            None if Builtin::from_str(name).is_ok() => Err(error(&format!(
+                "{} is a builtin and can't be assigned",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+}
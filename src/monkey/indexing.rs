@@ -0,0 +1,21 @@
+//Index math shared by both engines, negative indices count from the end
+
+pub fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index < 0 || index as usize >= len {
+        None
+    } else {
+        Some(index as usize)
+    }
+}
+
+//Out of range bounds are clamped, so slicing never fails
+pub fn slice_bounds(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+    let clamp = |i: i64| {
+        let i = if i < 0 { len as i64 + i } else { i };
+        i.clamp(0, len as i64) as usize
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
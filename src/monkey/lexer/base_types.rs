@@ -1,31 +1,66 @@
-use std::iter::Peekable;
+use std::collections::{HashSet, VecDeque};
 
-use super::main::{TokenIterator, Tokenizer};
+use super::main::{split_operator, TokenIterator, Tokenizer};
 use crate::monkey::lexer::tokens::Token;
 
 pub struct Lexer<I: TokenIterator> {
-    tokenizer: Peekable<Tokenizer<I>>,
+    tokenizer: Tokenizer<I>,
+    //tokens read ahead, operator runs are split into these
+    pending: VecDeque<Token>,
+    //operators declared so far, other runs are split into builtin ones
+    operators: HashSet<String>,
 }
 
 impl<I: TokenIterator> Lexer<I> {
     pub fn new(input: I) -> Self {
         Self {
-            tokenizer: Tokenizer::new(input).peekable(),
+            tokenizer: Tokenizer::new(input),
+            pending: VecDeque::new(),
+            operators: HashSet::new(),
+        }
+    }
+    fn fill(&mut self) {
+        if !self.pending.is_empty() {
+            return;
+        }
+        match self.tokenizer.next() {
+            Some(Token::Operator(op)) if !self.operators.contains(&op) => {
+                self.pending.extend(split_operator(&op))
+            }
+            Some(token) => self.pending.push_back(token),
+            None => {}
         }
     }
     pub fn peek(&mut self) -> Option<&Token> {
-        self.tokenizer.peek()
+        self.fill();
+        self.pending.front()
     }
     pub fn next_if_eq(&mut self, token: &Token) -> Option<Token> {
-        self.tokenizer.next_if(|t| token == t)
+        self.next_if(|t| token == t)
     }
     pub fn next_if(&mut self, f: impl Fn(&Token) -> bool) -> Option<Token> {
-        self.tokenizer.next_if(f)
+        if f(self.peek()?) {
+            self.pending.pop_front()
+        } else {
+            None
+        }
+    }
+    //The next token with operator runs left whole, for the operator being
+    //declared. Nothing may have been peeked past the token before it
+    pub fn next_operator(&mut self) -> Option<Token> {
+        match self.pending.pop_front() {
+            Some(token) => Some(token),
+            None => self.tokenizer.next(),
+        }
+    }
+    pub fn declare_operator(&mut self, op: String) {
+        self.operators.insert(op);
     }
 }
 impl<I: TokenIterator> Iterator for Lexer<I> {
     type Item = Token;
     fn next(&mut self) -> Option<Self::Item> {
-        self.tokenizer.next()
+        self.fill();
+        self.pending.pop_front()
     }
 }
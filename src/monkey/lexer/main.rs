@@ -3,7 +3,45 @@ use std::{
     str::FromStr,
 };
 
-use super::tokens::{Keyword, Token};
+use super::tokens::{Keyword, TemplatePart, Token};
+//Runs of these make up operators, user defined ones included
+fn is_operator_char(b: u8) -> bool {
+    b"+-*/<>=!%&|^~?@".contains(&b)
+}
+
+pub fn builtin_operator(op: &str) -> Option<Token> {
+    match op {
+        "+" => Some(Token::Plus),
+        "-" => Some(Token::Minus),
+        "*" => Some(Token::Star),
+        "/" => Some(Token::Slash),
+        ">" => Some(Token::Gt),
+        "<" => Some(Token::Lt),
+        "=" => Some(Token::Assign),
+        "==" => Some(Token::Eq),
+        "!" => Some(Token::Bang),
+        "!=" => Some(Token::Neq),
+        "%" => Some(Token::Percent),
+        _ => None,
+    }
+}
+
+//Splits a run that isn't a declared operator back into the builtin ones,
+//so `x=-1` still reads as `x = -1`
+pub fn split_operator(run: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = run;
+    while !rest.is_empty() {
+        let (token, len) = match rest.get(..2).and_then(builtin_operator) {
+            Some(token) => (token, 2),
+            None => (builtin_operator(&rest[..1]).unwrap_or(Token::Illegal), 1),
+        };
+        tokens.push(token);
+        rest = &rest[len..];
+    }
+    tokens
+}
+
 pub trait TokenIterator: Iterator<Item = u8> {}
 
 impl<T: Iterator<Item = u8>> TokenIterator for T {}
@@ -17,6 +55,52 @@ impl<I: TokenIterator> Tokenizer<I> {
             input: input.peekable(),
         }
     }
+    //Reads the source of a `${}` block up to its closing brace, skipping over
+    //braces nested inside it or inside string literals
+    fn interpolated_code(&mut self) -> Option<String> {
+        let mut code = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+        loop {
+            let b = self.input.next()?;
+            match b {
+                //an escaped char can't end the string
+                b'\\' if in_string => {
+                    code.push('\\');
+                    let escaped = self.input.next()?;
+                    code.push(self.char(escaped));
+                    continue;
+                }
+                b'"' => in_string = !in_string,
+                b'{' if !in_string => depth += 1,
+                b'}' if !in_string && depth == 0 => return Some(code),
+                b'}' if !in_string => depth -= 1,
+                _ => {}
+            }
+            code.push(self.char(b));
+        }
+    }
+    //The input is bytes, so chars outside ASCII are put back together from
+    //their continuation bytes
+    fn char(&mut self, first: u8) -> char {
+        let len = match first {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            match self.input.next_if(|b| b & 0xC0 == 0x80) {
+                Some(b) => bytes.push(b),
+                None => break,
+            }
+        }
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
     fn consume_whitespace(&mut self) {
         loop {
             if self
@@ -36,29 +120,12 @@ impl<I: TokenIterator> Iterator for Tokenizer<I> {
         //Consume whitespace
         self.consume_whitespace();
         match self.input.peek()? {
-            b'+' => {
-                self.input.next();
-                Some(Token::Plus)
-            }
-            b'-' => {
-                self.input.next();
-                Some(Token::Minus)
-            }
-            b'*' => {
-                self.input.next();
-                Some(Token::Star)
-            }
-            b'/' => {
-                self.input.next();
-                Some(Token::Slash)
-            }
-            b'>' => {
-                self.input.next();
-                Some(Token::Gt)
-            }
-            b'<' => {
-                self.input.next();
-                Some(Token::Lt)
+            b if is_operator_char(*b) => {
+                let mut run = String::new();
+                while let Some(b) = self.input.next_if(|b| is_operator_char(*b)) {
+                    run.push(b as char);
+                }
+                Some(builtin_operator(&run).unwrap_or(Token::Operator(run)))
             }
             b',' => {
                 self.input.next();
@@ -88,22 +155,6 @@ impl<I: TokenIterator> Iterator for Tokenizer<I> {
                 self.input.next();
                 Some(Token::Eof)
             }
-            b'=' => {
-                self.input.next();
-                if self.input.next_if(|b| *b == b'=').is_some() {
-                    Some(Token::Eq)
-                } else {
-                    Some(Token::Assign)
-                }
-            }
-            b'!' => {
-                self.input.next();
-                if self.input.next_if(|b| *b == b'=').is_some() {
-                    Some(Token::Neq)
-                } else {
-                    Some(Token::Bang)
-                }
-            }
             b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
                 let mut ident = String::new();
                 while let Some(b) = self.input.peek() {
@@ -135,13 +186,38 @@ impl<I: TokenIterator> Iterator for Tokenizer<I> {
             b'"' => {
                 self.input.next();
                 let mut string = String::new();
-                while let Some(b) = self.input.peek() {
-                    if *b == b'"' {
-                        self.input.next();
-                        return Some(Token::String(string));
-                    } else {
-                        string.push(*b as char);
-                        self.input.next();
+                let mut parts = Vec::new();
+                while let Some(b) = self.input.next() {
+                    match b {
+                        b'"' if parts.is_empty() => return Some(Token::String(string)),
+                        b'"' => {
+                            if !string.is_empty() {
+                                parts.push(TemplatePart::Literal(string));
+                            }
+                            return Some(Token::Template(parts));
+                        }
+                        b'$' if self.input.next_if(|b| *b == b'{').is_some() => {
+                            if !string.is_empty() {
+                                parts.push(TemplatePart::Literal(std::mem::take(&mut string)));
+                            }
+                            match self.interpolated_code() {
+                                Some(code) => parts.push(TemplatePart::Code(code)),
+                                None => return Some(Token::Illegal),
+                            }
+                        }
+                        b'\\' => match self.input.next() {
+                            Some(b'n') => string.push('\n'),
+                            Some(b't') => string.push('\t'),
+                            Some(b'r') => string.push('\r'),
+                            Some(b @ (b'"' | b'\\' | b'$')) => string.push(b as char),
+                            //unknown escapes are kept as written
+                            Some(b) => {
+                                string.push('\\');
+                                string.push(self.char(b));
+                            }
+                            None => return Some(Token::Illegal),
+                        },
+                        _ => string.push(self.char(b)),
                     }
                 }
                 Some(Token::Illegal)
@@ -158,9 +234,15 @@ impl<I: TokenIterator> Iterator for Tokenizer<I> {
                 self.input.next();
                 Some(Token::Colon)
             }
-            b'%' => {
+            b'.' => {
                 self.input.next();
-                Some(Token::Percent)
+                if self.input.next_if(|b| *b == b'.').is_none() {
+                    Some(Token::Dot)
+                } else if self.input.next_if(|b| *b == b'.').is_some() {
+                    Some(Token::Ellipsis)
+                } else {
+                    Some(Token::Illegal)
+                }
             }
             _ => {
                 self.input.next();
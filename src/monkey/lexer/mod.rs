@@ -5,4 +5,5 @@ mod tokens;
 pub use base_types::Lexer;
 pub use main::TokenIterator;
 pub use tokens::Keyword;
+pub use tokens::TemplatePart;
 pub use tokens::Token;
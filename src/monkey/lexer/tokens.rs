@@ -6,6 +6,7 @@ pub enum Token {
     Ident(String),
     Int(String),
     String(String),
+    Template(Vec<TemplatePart>),
     //operators
     Assign,
     Plus,
@@ -18,6 +19,8 @@ pub enum Token {
     Gt,
     Lt,
     Percent,
+    //a run of operator characters that isn't a builtin operator
+    Operator(String),
     //delimiters
     Comma,
     Semicolon,
@@ -28,6 +31,8 @@ pub enum Token {
     LBracket,
     RBracket,
     Colon,
+    Dot,
+    Ellipsis,
     //terminators
     Illegal,
     Eof,
@@ -40,6 +45,16 @@ impl Display for Token {
             Token::Ident(i) => write!(f, "{}", i),
             Token::Int(i) => write!(f, "{}", i),
             Token::String(s) => write!(f, "'{}'", s),
+            Token::Template(parts) => {
+                write!(f, "'")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(l) => write!(f, "{}", l)?,
+                        TemplatePart::Code(c) => write!(f, "${{{}}}", c)?,
+                    }
+                }
+                write!(f, "'")
+            }
             Token::Assign => write!(f, "="),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
@@ -60,12 +75,23 @@ impl Display for Token {
             Token::RBracket => write!(f, "]"),
             Token::Colon => write!(f, ":"),
             Token::Percent => write!(f, "%"),
+            Token::Operator(o) => write!(f, "{}", o),
+            Token::Dot => write!(f, "."),
+            Token::Ellipsis => write!(f, "..."),
             Token::Illegal => write!(f, "Illegal"),
             Token::Eof => write!(f, ""),
         }
     }
 }
 
+//A piece of an interpolated string, the code inside `${}` is kept as source
+//and parsed as an expression by the parser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplatePart {
+    Literal(String),
+    Code(String),
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Keyword {
     Function,
@@ -75,6 +101,15 @@ pub enum Keyword {
     True,
     False,
     Return,
+    Try,
+    Catch,
+    Throw,
+    Struct,
+    Gen,
+    Yield,
+    Infixl,
+    Infixr,
+    Const,
 }
 impl Display for Keyword {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -86,6 +121,15 @@ impl Display for Keyword {
             Keyword::True => write!(f, "true"),
             Keyword::False => write!(f, "false"),
             Keyword::Return => write!(f, "return"),
+            Keyword::Try => write!(f, "try"),
+            Keyword::Catch => write!(f, "catch"),
+            Keyword::Throw => write!(f, "throw"),
+            Keyword::Struct => write!(f, "struct"),
+            Keyword::Gen => write!(f, "gen"),
+            Keyword::Yield => write!(f, "yield"),
+            Keyword::Infixl => write!(f, "infixl"),
+            Keyword::Infixr => write!(f, "infixr"),
+            Keyword::Const => write!(f, "const"),
         }
     }
 }
@@ -100,6 +144,15 @@ impl FromStr for Keyword {
             "true" => Ok(Keyword::True),
             "false" => Ok(Keyword::False),
             "return" => Ok(Keyword::Return),
+            "try" => Ok(Keyword::Try),
+            "catch" => Ok(Keyword::Catch),
+            "throw" => Ok(Keyword::Throw),
+            "struct" => Ok(Keyword::Struct),
+            "gen" => Ok(Keyword::Gen),
+            "yield" => Ok(Keyword::Yield),
+            "infixl" => Ok(Keyword::Infixl),
+            "infixr" => Ok(Keyword::Infixr),
+            "const" => Ok(Keyword::Const),
             _ => Err("Invalid keyword".to_string()),
         }
     }
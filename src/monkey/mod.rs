@@ -1,6 +1,14 @@
 pub mod evaluator;
+mod indexing;
 mod lexer;
 mod parser;
+mod prelude;
+mod regex;
+mod rng;
+mod stdlib;
+#[cfg(test)]
+mod tests;
 pub mod vm;
 pub use lexer::Lexer;
 pub use parser::{Parser, Program};
+pub use stdlib::{Clock, FixedClock, SystemClock};
@@ -1,7 +1,8 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use super::types::*;
-use crate::monkey::lexer::{Keyword, Lexer, Token, TokenIterator};
+use crate::monkey::lexer::{Keyword, Lexer, TemplatePart, Token, TokenIterator};
 
 const PREFIX_PRECEDENCE: u8 = 10;
 const LOWEST_PRECEDENCE: u8 = 0;
@@ -23,17 +24,43 @@ pub enum Error {
     BadCall,
     BadFn,
     BadHash,
+    BadInterpolation,
+    BadTry,
+    BadStruct,
+    BadAssign,
+    BadTuple,
+    BadYield,
+    BadOperator,
     NoToken,
 }
 type Result<T> = std::result::Result<T, Error>;
 
 pub struct Parser<I: TokenIterator> {
     tokens: Lexer<I>,
+    //whether a yield statement is allowed where we are
+    generator: bool,
+    //yield statements parsed so far
+    yields: usize,
+    //operators declared with infixl and infixr
+    operators: HashMap<String, Fixity>,
+}
+
+//Binding power of a declared operator, right associative operators parse
+//their right side at one less so `a ^ b ^ c` is `a ^ (b ^ c)`
+#[derive(Clone, Copy)]
+struct Fixity {
+    precedence: u8,
+    right: bool,
 }
 
 impl<I: TokenIterator> Parser<I> {
     pub fn new(tokens: Lexer<I>) -> Self {
-        Self { tokens }
+        Self {
+            tokens,
+            generator: false,
+            yields: 0,
+            operators: HashMap::new(),
+        }
     }
     pub fn program(&mut self) -> Result<Program> {
         let mut stmts = Vec::new();
@@ -43,15 +70,66 @@ impl<I: TokenIterator> Parser<I> {
         Ok(Program { statements: stmts })
     }
     fn statement(&mut self) -> Result<Expr> {
-        match self.tokens.peek().unwrap() {
-            Token::Keyword(Keyword::Let) => self.let_(),
+        let generator = self.generator;
+        let yields = self.yields;
+        //yield can't be nested in expressions, only in if and try statements
+        self.generator = generator
+            && matches!(
+                self.tokens.peek(),
+                Some(Token::Keyword(Keyword::If | Keyword::Try | Keyword::Yield))
+            );
+        let ret = match self.tokens.peek().unwrap() {
+            Token::Keyword(Keyword::Let | Keyword::Const) => self.let_(),
             Token::Keyword(Keyword::Return) => self.return_(),
+            Token::Keyword(Keyword::Struct) => self.struct_(),
+            Token::Keyword(Keyword::Yield) => self.yield_(),
+            Token::Keyword(Keyword::Infixl | Keyword::Infixr) => self.operator(),
             _ => {
-                let ret = self.expr(LOWEST_PRECEDENCE);
+                let mut ret = self.expr(LOWEST_PRECEDENCE)?;
+                //`fn name(..) { .. }` on its own declares `name`
+                if let Expr::Fn(f) = &ret {
+                    if let Some(name) = &f.name {
+                        ret = Expr::Let(Let {
+                            name: name.clone(),
+                            value: Box::new(ret.clone()),
+                            constant: false,
+                        });
+                    }
+                }
+                if self.next_if_assign().is_ok() {
+                    ret = self.assign(ret)?;
+                }
                 //Optional semicolon at the end of expresion statement
                 let _ = self.next_if_semicolon();
-                ret
+                Ok(ret)
             }
+        };
+        self.generator = generator;
+        let ret = ret?;
+        if self.yields != yields
+            && !matches!(
+                ret,
+                Expr::If(_) | Expr::Try(_) | Expr::Yield(_) | Expr::Delegate(_)
+            )
+        {
+            return Err(Error::BadYield);
+        }
+        Ok(ret)
+    }
+    fn yield_(&mut self) -> Result<Expr> {
+        self.tokens.next();
+        if !self.generator {
+            return Err(Error::BadYield);
+        }
+        self.generator = false;
+        self.yields += 1;
+        let delegate = self.tokens.next_if_eq(&Token::Star).is_some();
+        let value = Box::new(self.expr(LOWEST_PRECEDENCE)?);
+        let _ = self.next_if_semicolon();
+        if delegate {
+            Ok(Expr::Delegate(value))
+        } else {
+            Ok(Expr::Yield(value))
         }
     }
     fn block(&mut self) -> Result<Block> {
@@ -81,31 +159,150 @@ impl<I: TokenIterator> Parser<I> {
         Ok(Rc::try_unwrap(left_expr).unwrap())
     }
 
+    //`(a)` is just grouping, `()`, `(a,)` and `(a, b)` are tuples
     fn grouped_expr(&mut self) -> Result<Expr> {
         self.tokens.next();
+        if self.tokens.next_if_eq(&Token::RParen).is_some() {
+            return Ok(Expr::Tuple(Vec::new()));
+        }
         let expr = self.expr(LOWEST_PRECEDENCE)?;
+        if self.tokens.next_if_eq(&Token::Comma).is_none() {
+            self.next_if_rparen()?;
+            return Ok(expr);
+        }
+        let mut elems = vec![expr];
+        while !matches!(self.peek()?, Token::RParen | Token::Eof) {
+            elems.push(self.expr(LOWEST_PRECEDENCE)?);
+            match self.peek()? {
+                Token::Comma => {
+                    self.tokens.next();
+                }
+                Token::RParen => {
+                    continue;
+                }
+                _ => {
+                    return Err(Error::BadTuple);
+                }
+            }
+        }
+        self.next_if_rparen()?;
+        Ok(Expr::Tuple(elems))
+    }
+    fn destructure(&mut self) -> Result<Expr> {
+        self.next_if_lparen()?;
+        let mut names = Vec::new();
+        while !matches!(self.peek()?, Token::RParen | Token::Eof) {
+            match self.tokens.next() {
+                Some(Token::Ident(name)) if !names.contains(&name) => names.push(name),
+                _ => return Err(Error::BadTuple),
+            }
+            match self.peek()? {
+                Token::Comma => {
+                    self.tokens.next();
+                }
+                Token::RParen => {
+                    continue;
+                }
+                _ => {
+                    return Err(Error::BadTuple);
+                }
+            }
+        }
         self.next_if_rparen()?;
-        Ok(expr)
+        self.next_if_assign()?;
+        let value = Box::new(self.expr(LOWEST_PRECEDENCE)?);
+        self.next_if_semicolon()?;
+        Ok(Expr::Destructure(Destructure { names, value }))
     }
 
     fn let_(&mut self) -> Result<Expr> {
-        self.next_if_let()?;
+        let constant = self.tokens.next() == Some(Token::Keyword(Keyword::Const));
+        if !constant && self.peek()? == &Token::LParen {
+            return self.destructure();
+        }
         let name = if let Token::Ident(n) = self.next_if_ident()? {
             n
         } else {
             return Err(Error::ExpectedToken(Token::Ident("".to_string())));
         };
         self.next_if_assign()?;
-        let mut value = Box::new(self.expr(LOWEST_PRECEDENCE)?);
+        let value = self.expr(LOWEST_PRECEDENCE)?;
         self.next_if_semicolon()?;
-        if let Expr::Fn(fn_) = value.as_ref() {
-            value = Box::new(Expr::Fn(Rc::new(Fn {
-                name: Some(name.clone()),
-                body: fn_.body.clone(),
-                args: fn_.args.clone(),
-            })));
+        let value = Box::new(name_fn(value, &name));
+        Ok(Expr::Let(Let {
+            name,
+            value,
+            constant,
+        }))
+    }
+    //`infixl 6 <+> = fn(a, b) { .. };` binds the function to the operator,
+    //uses of it after the declaration become calls
+    fn operator(&mut self) -> Result<Expr> {
+        let right = self.tokens.next() == Some(Token::Keyword(Keyword::Infixr));
+        let precedence = match self.tokens.next() {
+            Some(Token::Int(p)) => p.parse::<u8>().ok().filter(|p| (1..=9).contains(p)),
+            _ => None,
+        }
+        .ok_or(Error::BadOperator)?;
+        let name = match self.tokens.next_operator() {
+            Some(Token::Operator(name)) => name,
+            _ => return Err(Error::BadOperator),
+        };
+        self.tokens.declare_operator(name.clone());
+        self.operators
+            .insert(name.clone(), Fixity { precedence, right });
+        self.next_if_assign()?;
+        let value = self.expr(LOWEST_PRECEDENCE)?;
+        self.next_if_semicolon()?;
+        let value = Box::new(name_fn(value, &name));
+        Ok(Expr::Let(Let {
+            name,
+            value,
+            constant: false,
+        }))
+    }
+    fn struct_(&mut self) -> Result<Expr> {
+        self.tokens.next();
+        let name = match self.next_if_ident()? {
+            Token::Ident(name) => name,
+            _ => unreachable!(),
+        };
+        self.next_if_lbrace()?;
+        let mut fields = Vec::new();
+        while !matches!(self.peek()?, Token::RBrace | Token::Eof) {
+            match self.tokens.next() {
+                Some(Token::Ident(field)) if !fields.contains(&field) => fields.push(field),
+                _ => return Err(Error::BadStruct),
+            }
+            match self.peek()? {
+                Token::Comma => {
+                    self.tokens.next();
+                }
+                Token::RBrace => {
+                    continue;
+                }
+                _ => {
+                    return Err(Error::BadStruct);
+                }
+            }
+        }
+        self.next_if_rbrace()?;
+        let _ = self.next_if_semicolon();
+        Ok(Expr::Struct(Rc::new(Struct { name, fields })))
+    }
+    fn assign(&mut self, target: Expr) -> Result<Expr> {
+        fn assignable(target: &Expr) -> bool {
+            match target {
+                Expr::Identifier(_) => true,
+                Expr::Field(f) => assignable(&f.left),
+                _ => false,
+            }
         }
-        Ok(Expr::Let(Let { name, value }))
+        if !matches!(target, Expr::Field(_)) || !assignable(&target) {
+            return Err(Error::BadAssign);
+        }
+        let value = self.expr(LOWEST_PRECEDENCE)?;
+        Ok(Expr::Assign(Box::new(Assign { target, value })))
     }
     fn return_(&mut self) -> Result<Expr> {
         self.next_if_return()?;
@@ -121,7 +318,11 @@ impl<I: TokenIterator> Parser<I> {
         self.next_if_if()?;
 
         self.next_if_lparen()?;
-        let condition = self.expr(LOWEST_PRECEDENCE)?;
+        let generator = self.generator;
+        self.generator = false;
+        let condition = self.expr(LOWEST_PRECEDENCE);
+        self.generator = generator;
+        let condition = condition?;
         self.next_if_rparen()?;
 
         let body = self.block()?;
@@ -141,6 +342,25 @@ impl<I: TokenIterator> Parser<I> {
             }))
         }
     }
+    fn try_(&mut self) -> Result<Expr> {
+        self.tokens.next();
+        let body = self.block()?;
+        self.tokens
+            .next_if_eq(&Token::Keyword(Keyword::Catch))
+            .ok_or(Error::BadTry)?;
+        self.next_if_lparen()?;
+        let name = match self.next_if_ident()? {
+            Token::Ident(name) => name,
+            _ => unreachable!(),
+        };
+        self.next_if_rparen()?;
+        let handler = self.block()?;
+        Ok(Expr::Try(Try {
+            body,
+            name,
+            handler,
+        }))
+    }
     fn prefix(&mut self) -> Result<Expr> {
         let token = self.tokens.peek();
         if let Some(token) = token {
@@ -159,6 +379,10 @@ impl<I: TokenIterator> Parser<I> {
                     Some(Token::String(t)) => Ok(Expr::String(t)),
                     _ => unreachable!(),
                 },
+                Token::Template(_) => match self.tokens.next() {
+                    Some(Token::Template(parts)) => self.interpolation(parts),
+                    _ => unreachable!(),
+                },
                 Token::Keyword(Keyword::True) => {
                     self.tokens.next();
                     Ok(Expr::Bool(true))
@@ -176,7 +400,12 @@ impl<I: TokenIterator> Parser<I> {
                     })))
                 }
                 Token::Keyword(Keyword::If) => self.if_(),
-                Token::Keyword(Keyword::Function) => self.fn_(),
+                Token::Keyword(Keyword::Try) => self.try_(),
+                Token::Keyword(Keyword::Throw) => {
+                    self.tokens.next();
+                    Ok(Expr::Throw(Box::new(self.expr(LOWEST_PRECEDENCE)?)))
+                }
+                Token::Keyword(Keyword::Function | Keyword::Gen) => self.fn_(),
                 Token::LBracket => self.array(),
                 Token::LBrace => self.hash(),
                 _ => Err(Error::BadPrefix),
@@ -203,6 +432,18 @@ impl<I: TokenIterator> Parser<I> {
                     let right = self.expr(precedence)?;
                     Ok(Some(Expr::Infix(Box::new(Infix { left, token, right }))))
                 }
+                Token::Operator(_) => {
+                    let name = match self.tokens.next() {
+                        Some(Token::Operator(name)) => name,
+                        _ => unreachable!(),
+                    };
+                    let fixity = *self.operators.get(&name).ok_or(Error::BadOperator)?;
+                    let right = self.expr(fixity.precedence - fixity.right as u8)?;
+                    Ok(Some(Expr::Call(Call {
+                        expr: Rc::new(Expr::Identifier(name)),
+                        args: vec![Rc::unwrap_or_clone(left), right],
+                    })))
+                }
                 Token::LParen => {
                     let expr = self.call(left)?;
                     Ok(Some(expr))
@@ -211,6 +452,17 @@ impl<I: TokenIterator> Parser<I> {
                     let expr = self.indexer(left)?;
                     Ok(Some(expr))
                 }
+                Token::Dot => {
+                    self.tokens.next();
+                    let name = match self.next_if_ident()? {
+                        Token::Ident(name) => name,
+                        _ => unreachable!(),
+                    };
+                    Ok(Some(Expr::Field(Box::new(Field {
+                        left: Rc::unwrap_or_clone(left),
+                        name,
+                    }))))
+                }
                 _ => Ok(None),
             }
         } else {
@@ -221,7 +473,7 @@ impl<I: TokenIterator> Parser<I> {
         let mut args = Vec::new();
         self.next_if_lparen()?;
         while !matches!(self.peek()?, Token::RParen | Token::Eof) {
-            if let Ok(expr) = self.expr(LOWEST_PRECEDENCE) {
+            if let Ok(expr) = self.element() {
                 args.push(expr);
                 match self.peek()? {
                     Token::Comma => {
@@ -242,44 +494,85 @@ impl<I: TokenIterator> Parser<I> {
         Ok(Expr::Call(Call { expr: left, args }))
     }
     fn fn_(&mut self) -> Result<Expr> {
+        let generator = self
+            .tokens
+            .next_if_eq(&Token::Keyword(Keyword::Gen))
+            .is_some();
         self.next_if_fn()?;
-        let mut args = Vec::new();
+        let name = match self.tokens.next_if(|t| matches!(t, Token::Ident(_))) {
+            Some(Token::Ident(name)) => Some(name),
+            _ => None,
+        };
+        let mut args: Vec<Arg> = Vec::new();
+        let mut rest = None;
         self.next_if_lparen()?;
         while !matches!(self.peek()?, Token::RParen | Token::Eof) {
-            if matches!(self.peek()?, Token::Ident(_)) {
-                match self.tokens.next() {
-                    Some(Token::Ident(i)) => args.push(i),
-                    _ => unreachable!(),
+            //a rest parameter has to be the last one
+            if rest.is_some() {
+                return Err(Error::BadFn);
+            }
+            let variadic = self.tokens.next_if_eq(&Token::Ellipsis).is_some();
+            let name = match self.tokens.next() {
+                Some(Token::Ident(i)) => i,
+                _ => return Err(Error::BadFn),
+            };
+            if variadic {
+                rest = Some(name);
+            } else if self.next_if_assign().is_ok() {
+                let default = self.expr(LOWEST_PRECEDENCE)?;
+                args.push(Arg {
+                    name,
+                    default: Some(default),
+                });
+            } else if args.last().is_some_and(|a| a.default.is_some()) {
+                //required args can't follow optional ones
+                return Err(Error::BadFn);
+            } else {
+                args.push(Arg {
+                    name,
+                    default: None,
+                });
+            }
+            match self.peek()? {
+                Token::Comma => {
+                    self.tokens.next();
                 }
-                match self.peek()? {
-                    Token::Comma => {
-                        self.tokens.next();
-                    }
-                    Token::RParen => {
-                        continue;
-                    }
-                    _ => {
-                        return Err(Error::BadFn);
-                    }
+                Token::RParen => {
+                    continue;
+                }
+                _ => {
+                    return Err(Error::BadFn);
                 }
-            } else {
-                return Err(Error::BadFn);
             }
         }
         self.next_if_rparen()?;
-        let body = self.block()?;
+        //yields in the body belong to this fn, not the statement around it
+        let (outer, yields) = (self.generator, self.yields);
+        self.generator = generator;
+        let body = self.block();
+        (self.generator, self.yields) = (outer, yields);
         Ok(Expr::Fn(Rc::new(Fn {
             args,
-            body,
-            name: None,
+            rest,
+            body: body?,
+            name,
+            generator,
         })))
     }
 
+    //An array element or call argument, which can be spread
+    fn element(&mut self) -> Result<Expr> {
+        if self.tokens.next_if_eq(&Token::Ellipsis).is_some() {
+            Ok(Expr::Spread(Box::new(self.expr(LOWEST_PRECEDENCE)?)))
+        } else {
+            self.expr(LOWEST_PRECEDENCE)
+        }
+    }
     fn array(&mut self) -> Result<Expr> {
         let mut elemns = Vec::new();
         self.next_if_lbracket()?;
         while !matches!(self.peek()?, Token::RBracket | Token::Eof) {
-            let expr = self.expr(LOWEST_PRECEDENCE);
+            let expr = self.element();
             if let Ok(expr) = expr {
                 elemns.push(expr);
                 match self.peek()? {
@@ -298,24 +591,69 @@ impl<I: TokenIterator> Parser<I> {
         self.next_if_rbracket()?;
         Ok(Expr::Array(elemns))
     }
+    fn interpolation(&mut self, parts: Vec<TemplatePart>) -> Result<Expr> {
+        let mut exprs = Vec::new();
+        for part in parts {
+            match part {
+                TemplatePart::Literal(l) => exprs.push(Expr::String(l)),
+                TemplatePart::Code(code) => {
+                    let mut parser = Parser::new(Lexer::new(code.bytes()));
+                    //operators declared before the string work inside it too
+                    for (name, fixity) in &self.operators {
+                        parser.tokens.declare_operator(name.clone());
+                        parser.operators.insert(name.clone(), *fixity);
+                    }
+                    let expr = parser
+                        .expr(LOWEST_PRECEDENCE)
+                        .map_err(|_| Error::BadInterpolation)?;
+                    if !matches!(parser.tokens.peek(), Some(Token::Eof) | None) {
+                        return Err(Error::BadInterpolation);
+                    }
+                    exprs.push(expr);
+                }
+            }
+        }
+        Ok(Expr::Interpolation(exprs))
+    }
     fn indexer(&mut self, left: Rc<Expr>) -> Result<Expr> {
         self.next_if_lbracket()?;
-        let index = self.expr(LOWEST_PRECEDENCE)?;
+        let index = if matches!(self.peek()?, Token::Colon) {
+            None
+        } else {
+            Some(self.expr(LOWEST_PRECEDENCE)?)
+        };
+        if self.next_if_colon().is_ok() {
+            let end = if matches!(self.peek()?, Token::RBracket) {
+                None
+            } else {
+                Some(self.expr(LOWEST_PRECEDENCE)?)
+            };
+            self.next_if_rbracket()?;
+            return Ok(Expr::Slice(Box::new(Slice {
+                left: Rc::unwrap_or_clone(left),
+                start: index,
+                end,
+            })));
+        }
         self.next_if_rbracket()?;
         Ok(Expr::Infix(Box::new(Infix {
             left,
             token: Token::LBracket,
-            right: index,
+            right: index.ok_or(Error::BadPrefix)?,
         })))
     }
     fn hash(&mut self) -> Result<Expr> {
         self.next_if_lbrace()?;
         let mut hash = Vec::new();
         while !matches!(self.tokens.peek(), Some(Token::RBrace) | None) {
-            let key = self.expr(LOWEST_PRECEDENCE)?;
-            self.next_if_colon()?;
-            let value = self.expr(LOWEST_PRECEDENCE)?;
-            hash.push((key, value));
+            if self.tokens.next_if_eq(&Token::Ellipsis).is_some() {
+                hash.push(HashEntry::Spread(self.expr(LOWEST_PRECEDENCE)?));
+            } else {
+                let key = self.expr(LOWEST_PRECEDENCE)?;
+                self.next_if_colon()?;
+                let value = self.expr(LOWEST_PRECEDENCE)?;
+                hash.push(HashEntry::Pair(key, value));
+            }
             match self.peek()? {
                 Token::Comma => {
                     self.tokens.next();
@@ -333,25 +671,24 @@ impl<I: TokenIterator> Parser<I> {
     }
     fn current_precedence(&mut self) -> u8 {
         match self.tokens.peek() {
-            Some(Token::Eq) => 1,
-            Some(Token::Neq) => 1,
-            Some(Token::Lt) => 2,
-            Some(Token::Gt) => 2,
-            Some(Token::Plus) => 3,
-            Some(Token::Minus) => 3,
-            Some(Token::Star) => 4,
-            Some(Token::Slash) => 4,
-            Some(Token::Percent) => 4,
+            //declared operators go from 1 to 9 around these
+            Some(Token::Eq) => 4,
+            Some(Token::Neq) => 4,
+            Some(Token::Lt) => 5,
+            Some(Token::Gt) => 5,
+            Some(Token::Plus) => 6,
+            Some(Token::Minus) => 6,
+            Some(Token::Star) => 7,
+            Some(Token::Slash) => 7,
+            Some(Token::Percent) => 7,
+            //an undeclared one is left for infix to report
+            Some(Token::Operator(op)) => self.operators.get(op).map_or(100, |f| f.precedence),
             Some(Token::LParen) => CALL_PRECEDENCE,
             Some(Token::LBracket) => INDEXER_PRECEDENCE,
+            Some(Token::Dot) => INDEXER_PRECEDENCE,
             _ => 100,
         }
     }
-    pub fn next_if_let(&mut self) -> Result<Token> {
-        self.tokens
-            .next_if_eq(&Token::Keyword(Keyword::Let))
-            .ok_or(Error::ExpectedToken(Token::Keyword(Keyword::Let)))
-    }
     pub fn next_if_assign(&mut self) -> Result<Token> {
         self.tokens
             .next_if_eq(&Token::Assign)
@@ -427,3 +764,17 @@ impl<I: TokenIterator> Parser<I> {
         self.tokens.peek().ok_or(Error::NoToken)
     }
 }
+
+//A function bound to a name can call itself by that name
+fn name_fn(value: Expr, name: &str) -> Expr {
+    match value {
+        Expr::Fn(fn_) if fn_.name.is_none() => Expr::Fn(Rc::new(Fn {
+            name: Some(name.to_string()),
+            body: fn_.body.clone(),
+            args: fn_.args.clone(),
+            rest: fn_.rest.clone(),
+            generator: fn_.generator,
+        })),
+        value => value,
+    }
+}
@@ -11,14 +11,38 @@ pub enum Expr {
     Array(Vec<Expr>),
     Bool(bool),
     String(String),
+    //literal pieces are kept as `Expr::String`
+    Interpolation(Vec<Expr>),
     Prefix(Box<Prefix>),
     Infix(Box<Infix>),
+    Slice(Box<Slice>),
     If(If),
     Fn(Rc<Fn>),
     Call(Call),
     Let(Let),
     Return(Box<Expr>),
-    Hash(Vec<(Expr, Expr)>),
+    Hash(Vec<HashEntry>),
+    Try(Try),
+    Throw(Box<Expr>),
+    //only a statement, directly in a generator body or in its if/try blocks
+    Yield(Box<Expr>),
+    //`yield* g` yields everything another generator does
+    Delegate(Box<Expr>),
+    Struct(Rc<Struct>),
+    Field(Box<Field>),
+    Assign(Box<Assign>),
+    Tuple(Vec<Expr>),
+    //`let (a, b) = value;`
+    Destructure(Destructure),
+    //`...xs`, only an element of an array literal or a call argument
+    Spread(Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum HashEntry {
+    Pair(Expr, Expr),
+    //`...h` copies in every entry of another hash
+    Spread(Expr),
 }
 #[derive(Debug, PartialEq, Clone)]
 pub struct Prefix {
@@ -32,10 +56,26 @@ pub struct Infix {
     pub token: Token,
     pub right: Expr,
 }
+#[derive(Debug, PartialEq, Clone)]
+pub struct Slice {
+    pub left: Expr,
+    pub start: Option<Expr>,
+    pub end: Option<Expr>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Let {
     pub name: String,
     pub value: Box<Expr>,
+    //`const`, the name can't be assigned through or defined again in the
+    //same scope
+    pub constant: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Destructure {
+    pub names: Vec<String>,
+    pub value: Box<Expr>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -45,11 +85,55 @@ pub struct If {
     pub alternative: Option<Block>,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub left: Expr,
+    pub name: String,
+}
+
+//Only field updates (`p.x = 1`) are assignable, the target is a chain of
+//fields ending in an identifier
+#[derive(Debug, PartialEq, Clone)]
+pub struct Assign {
+    pub target: Expr,
+    pub value: Expr,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Try {
+    pub body: Block,
+    //the thrown value is bound to this name inside the handler
+    pub name: String,
+    pub handler: Block,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Arg {
+    pub name: String,
+    pub default: Option<Expr>,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Fn {
     pub name: Option<String>,
-    pub args: Vec<String>,
+    pub args: Vec<Arg>,
+    //collects any extra arguments into an array
+    pub rest: Option<String>,
     pub body: Block,
+    //`gen fn`, calling it returns a generator instead of running the body
+    pub generator: bool,
+}
+
+impl Fn {
+    pub fn num_required(&self) -> usize {
+        self.args.iter().filter(|a| a.default.is_none()).count()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -69,17 +153,27 @@ impl Display for Call {
     }
 }
 
+impl Display for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "{} = {}", self.name, default),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
 impl Display for Fn {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let ret = format!(
-            "fn({}) {{ {} }}",
-            self.args
-                .iter()
-                .map(|a| a.to_string())
-                .collect::<Vec<String>>()
-                .join(", "),
-            self.body
-        );
+        let mut args = self
+            .args
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<String>>();
+        if let Some(rest) = &self.rest {
+            args.push(format!("...{}", rest));
+        }
+        let gen = if self.generator { "gen " } else { "" };
+        let ret = format!("{}fn({}) {{ {} }}", gen, args.join(", "), self.body);
         write!(f, "{}", ret)
     }
 }
@@ -93,9 +187,30 @@ impl Display for If {
         write!(f, "{}", ret)
     }
 }
+impl Display for Struct {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "struct {} {{ {} }}", self.name, self.fields.join(", "))
+    }
+}
+
+impl Display for Try {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "try {{ {} }} catch ({}) {{ {} }}",
+            self.body, self.name, self.handler
+        )
+    }
+}
 impl Display for Let {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "let {} = {}", self.name, self.value)
+        let keyword = if self.constant { "const" } else { "let" };
+        write!(f, "{} {} = {}", keyword, self.name, self.value)
+    }
+}
+impl Display for Destructure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "let ({}) = {}", self.names.join(", "), self.value)
     }
 }
 impl Display for Infix {
@@ -104,6 +219,20 @@ impl Display for Infix {
     }
 }
 
+impl Display for Slice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[", self.left)?;
+        if let Some(start) = &self.start {
+            write!(f, "{}", start)?;
+        }
+        write!(f, ":")?;
+        if let Some(end) = &self.end {
+            write!(f, "{}", end)?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl Display for Prefix {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}({})", self.token, self.right)
@@ -117,14 +246,41 @@ impl Display for Expr {
             Expr::Int(i) => write!(f, "{}", i),
             Expr::Prefix(p) => write!(f, "{}", p),
             Expr::Infix(i) => write!(f, "{}", i),
+            Expr::Slice(s) => write!(f, "{}", s),
             Expr::Bool(b) => write!(f, "{}", b),
             Expr::If(i) => write!(f, "{}", i),
             Expr::Fn(fn_) => write!(f, "{}", fn_),
             Expr::Call(e) => write!(f, "as eval ({})", e),
             Expr::Let(l) => write!(f, "{}", l),
             Expr::Return(e) => write!(f, "{}", e),
+            Expr::Try(t) => write!(f, "{}", t),
+            Expr::Throw(e) => write!(f, "throw {}", e),
+            Expr::Yield(e) => write!(f, "yield {}", e),
+            Expr::Delegate(e) => write!(f, "yield* {}", e),
+            Expr::Struct(s) => write!(f, "{}", s),
+            Expr::Field(field) => write!(f, "{}.{}", field.left, field.name),
+            Expr::Assign(a) => write!(f, "{} = {}", a.target, a.value),
             //Expr::Block(b) => write!(f, "{}", b),
             Expr::String(s) => write!(f, "{}", s),
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    match part {
+                        Expr::String(s) => write!(f, "{}", s)?,
+                        e => write!(f, "${{{}}}", e)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Tuple(t) if t.len() == 1 => write!(f, "({},)", t[0]),
+            Expr::Tuple(t) => write!(
+                f,
+                "({})",
+                t.iter()
+                    .map(|t| format!("{}", t))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Expr::Destructure(d) => write!(f, "{}", d),
             Expr::Array(a) => write!(
                 f,
                 "[{}]",
@@ -137,10 +293,14 @@ impl Display for Expr {
                 f,
                 "{{ {} }}",
                 h.iter()
-                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .map(|entry| match entry {
+                        HashEntry::Pair(k, v) => format!("{}: {}", k, v),
+                        HashEntry::Spread(h) => format!("...{}", h),
+                    })
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            Expr::Spread(e) => write!(f, "...{}", e),
         }
     }
 }
@@ -0,0 +1,18 @@
+//Helpers written in Monkey itself, both engines load them before user code
+use super::{Lexer, Parser, Program};
+use std::rc::Rc;
+
+const SOURCE: &str = include_str!("prelude.monkey");
+
+thread_local! {
+    //parsed once, every env and machine reuses the same program
+    static PROGRAM: Rc<Program> = Rc::new(
+        Parser::new(Lexer::new(SOURCE.bytes()))
+            .program()
+            .expect("Prelude should parse"),
+    );
+}
+
+pub fn program() -> Rc<Program> {
+    PROGRAM.with(Rc::clone)
+}
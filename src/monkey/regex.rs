@@ -0,0 +1,491 @@
+//A small regex engine for the regex builtins. Patterns compile to a program
+//for a Pike VM, which runs every way of matching side by side so the time
+//taken stays linear in the text however the pattern nests repetition.
+//Positions are counted in chars, like string indexing
+
+#[derive(Debug, Clone)]
+enum Item {
+    Range(char, char),
+    //`\d` and the like inside brackets
+    Class(Class),
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<Item>,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match item {
+            Item::Range(lo, hi) => (*lo..=*hi).contains(&c),
+            Item::Class(class) => class.matches(c),
+        });
+        found != self.negated
+    }
+    //`\d`, `\w`, `\s` and their negations
+    fn shorthand(c: char) -> Option<Self> {
+        let items = match c.to_ascii_lowercase() {
+            'd' => vec![Item::Range('0', '9')],
+            'w' => vec![
+                Item::Range('a', 'z'),
+                Item::Range('A', 'Z'),
+                Item::Range('0', '9'),
+                Item::Range('_', '_'),
+            ],
+            's' => [' ', '\t', '\n', '\r', '\u{b}', '\u{c}']
+                .map(|c| Item::Range(c, c))
+                .to_vec(),
+            _ => return None,
+        };
+        Some(Self {
+            negated: c.is_ascii_uppercase(),
+            items,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    //any char but a newline
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    //capturing groups have an index, the whole match is group 0
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+//Repetition counts past this are refused, each repeat copies the program
+const MAX_REPEAT: usize = 1000;
+const MAX_PROGRAM: usize = 100_000;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        Ok(c)
+    }
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.concat()?];
+        while self.eat('|') {
+            alts.push(self.concat()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            Node::Alt(alts)
+        })
+    }
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repeat(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+    fn repeat(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.counts()? {
+                Some(counts) => counts,
+                None => return Ok(node),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if matches!(node, Node::Assert(_) | Node::Empty) {
+            return Err("nothing to repeat".to_string());
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+    //`{n}`, `{n,}` or `{n,m}`, a brace that doesn't start one is a literal
+    fn counts(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let rest = self.chars[self.pos..].iter().collect::<String>();
+        let Some(end) = rest.find('}') else {
+            return Ok(None);
+        };
+        let inner = &rest[1..end];
+        let number = |s: &str| s.parse::<usize>().ok().filter(|n| *n <= MAX_REPEAT);
+        let counts = match inner.split_once(',') {
+            None => number(inner).map(|n| (n, Some(n))),
+            Some((min, "")) => number(min).map(|min| (min, None)),
+            Some((min, max)) => number(min)
+                .zip(number(max))
+                .map(|(min, max)| (min, Some(max))),
+        };
+        match counts {
+            Some((min, Some(max))) if min > max => Err("repeat counts out of order".to_string()),
+            Some(counts) => {
+                self.pos += inner.chars().count() + 2;
+                Ok(Some(counts))
+            }
+            None if inner.chars().all(|c| c.is_ascii_digit() || c == ',') && !inner.is_empty() => {
+                Err(format!("repeat counts have to be at most {}", MAX_REPEAT))
+            }
+            None => Ok(None),
+        }
+    }
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            '(' => {
+                let index = if self.eat('?') {
+                    if !self.eat(':') {
+                        return Err("unknown group kind".to_string());
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let inner = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(Node::Group(Box::new(inner), index))
+            }
+            '[' => self.class().map(Node::Class),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '\\' => match self.next()? {
+                'b' => Ok(Node::Assert(Assertion::WordBoundary)),
+                'B' => Ok(Node::Assert(Assertion::NotWordBoundary)),
+                c => Ok(match Class::shorthand(c) {
+                    Some(class) => Node::Class(class),
+                    None => Node::Char(escaped(c)),
+                }),
+            },
+            c @ ('*' | '+' | '?') => Err(format!("nothing to repeat before '{}'", c)),
+            ')' => Err("unmatched ')'".to_string()),
+            c => Ok(Node::Char(c)),
+        }
+    }
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        //a ']' right at the start is a literal
+        let mut first = true;
+        loop {
+            let c = self.next().map_err(|_| "missing ']'")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = match c {
+                '\\' => {
+                    let c = self.next()?;
+                    if let Some(class) = Class::shorthand(c) {
+                        items.push(Item::Class(class));
+                        continue;
+                    }
+                    escaped(c)
+                }
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let hi = match self.next()? {
+                    '\\' => escaped(self.next()?),
+                    c => c,
+                };
+                if hi < lo {
+                    return Err(format!("range {}-{} out of order", lo, hi));
+                }
+                items.push(Item::Range(lo, hi));
+            } else {
+                items.push(Item::Range(lo, lo));
+            }
+        }
+        Ok(Class { negated, items })
+    }
+}
+
+fn escaped(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    //tries the first branch before the second
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+    //capturing groups, not counting the whole match
+    groups: usize,
+}
+
+fn compile(node: &Node, prog: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => prog.push(Inst::Char(*c)),
+        Node::Any => prog.push(Inst::Any),
+        Node::Class(class) => prog.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => prog.push(Inst::Assert(*assertion)),
+        Node::Group(inner, None) => compile(inner, prog),
+        Node::Group(inner, Some(index)) => {
+            prog.push(Inst::Save(index * 2));
+            compile(inner, prog);
+            prog.push(Inst::Save(index * 2 + 1));
+        }
+        Node::Concat(nodes) => nodes.iter().for_each(|n| compile(n, prog)),
+        Node::Alt(alts) => {
+            let mut jumps = Vec::new();
+            for (ix, alt) in alts.iter().enumerate() {
+                if ix + 1 == alts.len() {
+                    compile(alt, prog);
+                    break;
+                }
+                let split = prog.len();
+                prog.push(Inst::Split(split + 1, 0));
+                compile(alt, prog);
+                jumps.push(prog.len());
+                prog.push(Inst::Jmp(0));
+                let next = prog.len();
+                prog[split] = Inst::Split(split + 1, next);
+            }
+            let end = prog.len();
+            for jump in jumps {
+                prog[jump] = Inst::Jmp(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            let split = |body: usize, out: usize| {
+                if *greedy {
+                    Inst::Split(body, out)
+                } else {
+                    Inst::Split(out, body)
+                }
+            };
+            for _ in 0..*min {
+                compile(node, prog);
+            }
+            match max {
+                None => {
+                    let start = prog.len();
+                    prog.push(Inst::Jmp(0));
+                    compile(node, prog);
+                    prog.push(Inst::Jmp(start));
+                    let end = prog.len();
+                    prog[start] = split(start + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(prog.len());
+                        prog.push(Inst::Jmp(0));
+                        compile(node, prog);
+                    }
+                    let end = prog.len();
+                    for at in splits {
+                        prog[at] = split(at + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_word(c: Option<&char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || *c == '_')
+}
+
+//Threads waiting on the same char, in the order they're preferred
+struct Threads {
+    //instructions already added for this position
+    seen: Vec<bool>,
+    list: Vec<(usize, Vec<Option<usize>>)>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Self {
+            seen: vec![false; len],
+            list: Vec::new(),
+        }
+    }
+    fn clear(&mut self) {
+        self.seen.iter_mut().for_each(|s| *s = false);
+        self.list.clear();
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched ')'".to_string());
+        }
+        let mut prog = vec![Inst::Save(0)];
+        compile(&node, &mut prog);
+        prog.push(Inst::Save(1));
+        prog.push(Inst::Match);
+        if prog.len() > MAX_PROGRAM {
+            return Err("pattern is too large".to_string());
+        }
+        Ok(Self {
+            prog,
+            groups: parser.groups,
+        })
+    }
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+    //Follows the instructions that don't read a char, adding the threads
+    //that wait on one
+    fn add(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        mut caps: Vec<Option<usize>>,
+        text: &[char],
+        pos: usize,
+    ) {
+        if threads.seen[pc] {
+            return;
+        }
+        threads.seen[pc] = true;
+        match &self.prog[pc] {
+            Inst::Jmp(to) => self.add(threads, *to, caps, text, pos),
+            Inst::Split(first, second) => {
+                self.add(threads, *first, caps.clone(), text, pos);
+                self.add(threads, *second, caps, text, pos);
+            }
+            Inst::Save(slot) => {
+                caps[*slot] = Some(pos);
+                self.add(threads, pc + 1, caps, text, pos);
+            }
+            Inst::Assert(assertion) => {
+                let holds = match assertion {
+                    Assertion::Start => pos == 0,
+                    Assertion::End => pos == text.len(),
+                    Assertion::WordBoundary | Assertion::NotWordBoundary => {
+                        let boundary = is_word(pos.checked_sub(1).and_then(|p| text.get(p)))
+                            != is_word(text.get(pos));
+                        boundary == matches!(assertion, Assertion::WordBoundary)
+                    }
+                };
+                if holds {
+                    self.add(threads, pc + 1, caps, text, pos);
+                }
+            }
+            _ => threads.list.push((pc, caps)),
+        }
+    }
+    //The leftmost match starting at or after `start`, as the start and end
+    //of the whole match followed by those of each group
+    pub fn find_at(&self, text: &[char], start: usize) -> Option<Vec<Option<usize>>> {
+        let mut current = Threads::new(self.prog.len());
+        let mut next = Threads::new(self.prog.len());
+        let mut matched = None;
+        for pos in start..=text.len() {
+            //a new attempt starting here, behind every older one
+            if matched.is_none() {
+                let caps = vec![None; (self.groups + 1) * 2];
+                self.add(&mut current, 0, caps, text, pos);
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+            for (pc, caps) in std::mem::take(&mut current.list) {
+                let step = match &self.prog[pc] {
+                    Inst::Char(c) => text.get(pos) == Some(c),
+                    Inst::Any => text.get(pos).is_some_and(|c| *c != '\n'),
+                    Inst::Class(class) => text.get(pos).is_some_and(|c| class.matches(*c)),
+                    Inst::Match => {
+                        //threads after this one are less preferred
+                        matched = Some(caps);
+                        break;
+                    }
+                    _ => unreachable!(),
+                };
+                if step {
+                    self.add(&mut next, pc + 1, caps, text, pos + 1);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        matched
+    }
+}
@@ -0,0 +1,32 @@
+//Small xorshift generator, the same seed always gives the same sequence so
+//runs that depend on it are reproducible
+
+pub const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        //xorshift gets stuck on a zero state
+        Self(if seed == 0 { DEFAULT_SEED } else { seed })
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    //A value in `0..n`, `n` has to be positive
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
@@ -0,0 +1,180 @@
+//Array builtins, they give back new arrays and leave the ones passed alone
+use super::{args, array, compare, equal, error, functions::merge_sort, Native, Value};
+use crate::monkey::indexing::slice_bounds;
+use std::collections::HashSet;
+
+//The first or last element, null for an empty array
+pub fn end<V: Value>(native: Native, a: Vec<V>, pick: fn(&[V]) -> Option<&V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let elems = array(native, &arr, "single array")?;
+    Ok(pick(&elems).cloned().unwrap_or_else(V::null))
+}
+
+pub fn rest<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let elems = array(native, &arr, "single array")?;
+    Ok(V::array(elems.into_iter().skip(1).collect()))
+}
+
+pub fn push<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr, elem] = args(native, a, "array and value")?;
+    let mut elems = array(native, &arr, "array and value")?;
+    elems.push(elem);
+    Ok(V::array(elems))
+}
+
+pub fn reverse<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let mut elems = array(native, &arr, "single array")?;
+    elems.reverse();
+    Ok(V::array(elems))
+}
+
+//Takes any number of arrays
+pub fn concat<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let mut joined = Vec::new();
+    for arr in a {
+        joined.extend(array(native, &arr, "arrays")?);
+    }
+    Ok(V::array(joined))
+}
+
+//`slice(arr, start, end)` like `arr[start:end]`, strings work too and a
+//missing or null bound means the start or the end
+pub fn slice<V: Value>(native: Native, mut a: Vec<V>) -> Result<V, V> {
+    let expected = "array or string and int bounds";
+    if a.len() == 2 {
+        a.push(V::null());
+    }
+    let [value, start, end] = args(native, a, expected)?;
+    let bound = |bound: &V| match bound.as_int() {
+        Some(i) => Ok(Some(i)),
+        None if bound.is_null() => Ok(None),
+        None => Err(error(native, expected)),
+    };
+    let (start, end) = (bound(&start)?, bound(&end)?);
+    if let Some(s) = value.as_str() {
+        let chars = s.chars().collect::<Vec<_>>();
+        let (start, end) = slice_bounds(start, end, chars.len());
+        return Ok(V::string(chars[start..end].iter().collect()));
+    }
+    let elems = array(native, &value, expected)?;
+    let (start, end) = slice_bounds(start, end, elems.len());
+    Ok(V::array(elems[start..end].to_vec()))
+}
+
+fn position<V: Value>(native: Native, a: Vec<V>) -> Result<Option<usize>, V> {
+    let [arr, elem] = args(native, a, "array and value")?;
+    let elems = array(native, &arr, "array and value")?;
+    Ok(elems.iter().position(|e| equal(e, &elem)))
+}
+
+pub fn contains<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    Ok(V::bool(position(native, a)?.is_some()))
+}
+
+//-1 when the array doesn't have it, like for strings
+pub fn index_of<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    Ok(V::int(position(native, a)?.map_or(-1, |ix| ix as i64)))
+}
+
+//Tuples of the elements at each position, as long as the shorter array
+pub fn zip<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [left, right] = args(native, a, "two arrays")?;
+    let left = array(native, &left, "two arrays")?;
+    let right = array(native, &right, "two arrays")?;
+    let pairs = left.into_iter().zip(right);
+    Ok(V::array(pairs.map(|(l, r)| V::tuple(vec![l, r])).collect()))
+}
+
+//Only one level, elements that aren't arrays are kept as they are
+pub fn flatten<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let mut flat = Vec::new();
+    for elem in array(native, &arr, "single array")? {
+        match elem.as_array() {
+            Some(inner) => flat.extend_from_slice(inner),
+            None => flat.push(elem),
+        }
+    }
+    Ok(V::array(flat))
+}
+
+//Keeps the first of equal elements, in the order they come in
+pub fn unique<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let mut seen = HashSet::new();
+    let mut kept: Vec<V> = Vec::new();
+    for elem in array(native, &arr, "single array")? {
+        let new = match elem.to_key() {
+            Some(key) => seen.insert(key),
+            None => !kept.iter().any(|k| equal(k, &elem)),
+        };
+        if new {
+            kept.push(elem);
+        }
+    }
+    Ok(V::array(kept))
+}
+
+pub fn sum<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "array of ints")?;
+    let mut total: i64 = 0;
+    for elem in array(native, &arr, "array of ints")? {
+        let i = elem
+            .as_int()
+            .ok_or_else(|| error(native, "array of ints"))?;
+        total = total
+            .checked_add(i)
+            .ok_or_else(|| V::error("Integer overflow in sum builtin"))?;
+    }
+    Ok(V::int(total))
+}
+
+//`range(end)`, `range(start, end)` or `range(start, end, step)`, the end
+//isn't included and a negative step counts down
+//Ranges longer than this are refused rather than filling memory
+const MAX_RANGE: i128 = 10_000_000;
+
+pub fn range<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let expected = "one to three ints with a non zero step";
+    let ints = a
+        .iter()
+        .map(|i| i.as_int().ok_or_else(|| error(native, expected)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (start, end, step) = match ints[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] if step != 0 => (start, end, step),
+        _ => return Err(error(native, expected)),
+    };
+    let (span, step_size) = (end as i128 - start as i128, step as i128);
+    let len = if span.signum() == step_size.signum() {
+        (span.abs() + step_size.abs() - 1) / step_size.abs()
+    } else {
+        0
+    };
+    if len > MAX_RANGE {
+        return Err(V::error(&format!(
+            "Range of {} elements is too large for range builtin, the limit is {}",
+            len, MAX_RANGE
+        )));
+    }
+    let mut elems = Vec::with_capacity(len as usize);
+    let mut i = start;
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        elems.push(V::int(i));
+        match i.checked_add(step) {
+            Some(next) => i = next,
+            None => break,
+        }
+    }
+    Ok(V::array(elems))
+}
+
+//Natural order, see `compare`
+pub fn sort<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let elems = array(native, &arr, "single array")?;
+    Ok(V::array(merge_sort(elems, &mut |a, b| Ok(compare(a, b)))?))
+}
@@ -0,0 +1,105 @@
+//Builtins taking a function, which they call back through the engine
+use super::{args, array, error, Context, Native, Value};
+use std::cmp::Ordering;
+
+//Calls a predicate, which has to give back a bool
+fn test<V: Value>(native: Native, ctx: &mut impl Context<V>, f: &V, elem: V) -> Result<bool, V> {
+    ctx.apply(f.clone(), vec![elem])?
+        .as_bool()
+        .ok_or_else(|| error(native, "function returning bool"))
+}
+
+pub fn map<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr, f] = args(native, a, "array and function")?;
+    let mapped = array(native, &arr, "array and function")?
+        .into_iter()
+        .map(|elem| ctx.apply(f.clone(), vec![elem]))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(V::array(mapped))
+}
+
+pub fn filter<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr, f] = args(native, a, "array and function")?;
+    let mut kept = Vec::new();
+    for elem in array(native, &arr, "array and function")? {
+        if test(native, ctx, &f, elem.clone())? {
+            kept.push(elem);
+        }
+    }
+    Ok(V::array(kept))
+}
+
+//`reduce(arr, initial, f)` folds from the left, calling `f(accumulated, elem)`
+pub fn reduce<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let expected = "array, initial value and function";
+    let [arr, initial, f] = args(native, a, expected)?;
+    array(native, &arr, expected)?
+        .into_iter()
+        .try_fold(initial, |acc, elem| ctx.apply(f.clone(), vec![acc, elem]))
+}
+
+//The first element passing, null when none does
+pub fn find<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr, f] = args(native, a, "array and function")?;
+    for elem in array(native, &arr, "array and function")? {
+        if test(native, ctx, &f, elem.clone())? {
+            return Ok(elem);
+        }
+    }
+    Ok(V::null())
+}
+
+//`any` and `all`, both stop at the first element deciding the answer
+pub fn quantify<V: Value>(
+    native: Native,
+    a: Vec<V>,
+    ctx: &mut impl Context<V>,
+    all: bool,
+) -> Result<V, V> {
+    let [arr, f] = args(native, a, "array and function")?;
+    for elem in array(native, &arr, "array and function")? {
+        if test(native, ctx, &f, elem)? != all {
+            return Ok(V::bool(!all));
+        }
+    }
+    Ok(V::bool(all))
+}
+
+//`sort_by(arr, f)` where `f(a, b)` is negative, zero or positive as `a` goes
+//before, with or after `b`
+pub fn sort_by<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr, f] = args(native, a, "array and function")?;
+    let elems = array(native, &arr, "array and function")?;
+    let sorted = merge_sort(elems, &mut |a: &V, b: &V| {
+        ctx.apply(f.clone(), vec![a.clone(), b.clone()])?
+            .as_int()
+            .map(|i| i.cmp(&0))
+            .ok_or_else(|| error(native, "function returning int"))
+    })?;
+    Ok(V::array(sorted))
+}
+
+//Stable, and stops at the first error from `cmp`, which `sort_by` can't
+pub fn merge_sort<V: Value>(
+    mut elems: Vec<V>,
+    cmp: &mut impl FnMut(&V, &V) -> Result<Ordering, V>,
+) -> Result<Vec<V>, V> {
+    if elems.len() <= 1 {
+        return Ok(elems);
+    }
+    let right = elems.split_off(elems.len() / 2);
+    let left = merge_sort(elems, cmp)?;
+    let right = merge_sort(right, cmp)?;
+    let mut sorted = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if cmp(r, l)? == Ordering::Less {
+            sorted.extend(right.next());
+        } else {
+            sorted.extend(left.next());
+        }
+    }
+    sorted.extend(left);
+    sorted.extend(right);
+    Ok(sorted)
+}
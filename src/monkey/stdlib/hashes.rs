@@ -0,0 +1,58 @@
+//Hash builtins, they return new hashes instead of changing the one passed
+use super::{args, error, sorted_entries, Native, Value};
+use std::collections::HashMap;
+
+fn hash<'a, V: Value>(
+    native: Native,
+    value: &'a V,
+    expected: &str,
+) -> Result<&'a HashMap<V::Key, V>, V> {
+    value.as_hash().ok_or_else(|| error(native, expected))
+}
+
+fn key<V: Value>(native: Native, value: &V, expected: &str) -> Result<V::Key, V> {
+    value.to_key().ok_or_else(|| error(native, expected))
+}
+
+//`keys`, `values` and `entries`, `f` turns a key and its value into an element
+pub fn entries<V: Value>(native: Native, a: Vec<V>, f: impl Fn(V, V) -> V) -> Result<V, V> {
+    let [h] = args(native, a, "single hash")?;
+    let entries = sorted_entries(hash(native, &h, "single hash")?);
+    Ok(V::array(
+        entries.into_iter().map(|(k, v)| f(k, v)).collect(),
+    ))
+}
+
+pub fn has<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [h, k] = args(native, a, "hash and key")?;
+    let h = hash(native, &h, "hash and key")?;
+    Ok(V::bool(h.contains_key(&key(native, &k, "hash and key")?)))
+}
+
+pub fn delete<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [h, k] = args(native, a, "hash and key")?;
+    let mut h = hash(native, &h, "hash and key")?.clone();
+    h.remove(&key(native, &k, "hash and key")?);
+    Ok(V::hash(h))
+}
+
+//Entries of the second hash win over the first's
+pub fn merge<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [h, other] = args(native, a, "two hashes")?;
+    let mut h = hash(native, &h, "two hashes")?.clone();
+    h.extend(hash(native, &other, "two hashes")?.clone());
+    Ok(V::hash(h))
+}
+
+//`get(h, k, default)` gives the default, or null without one, when `k` is missing
+pub fn get<V: Value>(native: Native, mut a: Vec<V>) -> Result<V, V> {
+    let expected = "hash, key and optional default";
+    if a.len() == 2 {
+        a.push(V::null());
+    }
+    let [h, k, default] = args(native, a, expected)?;
+    let h = hash(native, &h, expected)?;
+    Ok(h.get(&key(native, &k, expected)?)
+        .cloned()
+        .unwrap_or(default))
+}
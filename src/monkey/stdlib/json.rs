@@ -0,0 +1,257 @@
+//JSON builtins. Numbers have to be integers since that's all Monkey has
+use super::{args, string, text, Native, Value};
+use std::{collections::HashMap, fmt::Write, iter::Peekable, str::CharIndices};
+
+pub fn parse<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [s] = args(native, a, "single string")?;
+    let s = string(native, &s, "single string")?;
+    let mut parser = Parser {
+        chars: s.char_indices().peekable(),
+        end: s.len(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        Some((pos, _)) => Err(invalid("end of input", *pos)),
+        None => Ok(value),
+    }
+}
+
+fn invalid<V: Value>(expected: &str, pos: usize) -> V {
+    V::error(&format!("Invalid JSON, expected {} at {}", expected, pos))
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.end, |(pos, _)| *pos)
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if(|(_, next)| *next == c).is_some()
+    }
+    fn expect<V: Value>(&mut self, c: char, expected: &str) -> Result<(), V> {
+        self.skip_whitespace();
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(invalid(expected, self.pos()))
+        }
+    }
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+    fn value<V: Value>(&mut self) -> Result<V, V> {
+        self.skip_whitespace();
+        let pos = self.pos();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(V::string),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "true" => Ok(V::bool(true)),
+                    "false" => Ok(V::bool(false)),
+                    "null" => Ok(V::null()),
+                    _ => Err(invalid("a value", pos)),
+                }
+            }
+            _ => Err(invalid("a value", pos)),
+        }
+    }
+    fn number<V: Value>(&mut self) -> Result<V, V> {
+        let pos = self.pos();
+        let mut digits = String::new();
+        if self.eat('-') {
+            digits.push('-');
+        }
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+        if matches!(self.peek(), Some('.' | 'e' | 'E')) {
+            return Err(invalid("an integer", pos));
+        }
+        digits
+            .parse()
+            .map(V::int)
+            .map_err(|_| invalid("an integer that fits in 64 bits", pos))
+    }
+    fn string<V: Value>(&mut self) -> Result<String, V> {
+        self.expect('"', "a string")?;
+        let mut s = String::new();
+        loop {
+            let pos = self.pos();
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => s.push(self.escape()?),
+                Some((_, c)) if c >= ' ' => s.push(c),
+                _ => return Err(invalid("a closing quote", pos)),
+            }
+        }
+    }
+    fn escape<V: Value>(&mut self) -> Result<char, V> {
+        let pos = self.pos();
+        let c = match self.chars.next() {
+            Some((_, c @ ('"' | '\\' | '/'))) => c,
+            Some((_, 'b')) => '\u{8}',
+            Some((_, 'f')) => '\u{c}',
+            Some((_, 'n')) => '\n',
+            Some((_, 'r')) => '\r',
+            Some((_, 't')) => '\t',
+            Some((_, 'u')) => {
+                let mut code = self.hex()?;
+                //chars outside the basic plane come as a surrogate pair
+                if (0xD800..0xDC00).contains(&code) {
+                    if !(self.eat('\\') && self.eat('u')) {
+                        return Err(invalid("a low surrogate", self.pos()));
+                    }
+                    let low = self.hex()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(invalid("a low surrogate", pos));
+                    }
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                }
+                return char::from_u32(code).ok_or_else(|| invalid("a valid escape", pos));
+            }
+            _ => return Err(invalid("a valid escape", pos)),
+        };
+        Ok(c)
+    }
+    fn hex<V: Value>(&mut self) -> Result<u32, V> {
+        let pos = self.pos();
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.chars.next().and_then(|(_, c)| c.to_digit(16));
+            code = code * 16 + digit.ok_or_else(|| invalid("four hex digits", pos))?;
+        }
+        Ok(code)
+    }
+    fn array<V: Value>(&mut self) -> Result<V, V> {
+        self.expect('[', "an array")?;
+        let mut elems = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(V::array(elems));
+        }
+        loop {
+            elems.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(V::array(elems));
+            }
+            self.expect(',', "',' or ']'")?;
+        }
+    }
+    //a key given twice keeps the last value
+    fn object<V: Value>(&mut self) -> Result<V, V> {
+        self.expect('{', "an object")?;
+        let mut entries = HashMap::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(V::hash(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = V::string(self.string()?);
+            self.expect(':', "':'")?;
+            let value = self.value()?;
+            entries.insert(key.to_key().unwrap(), value);
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(V::hash(entries));
+            }
+            self.expect(',', "',' or '}'")?;
+        }
+    }
+}
+
+//Compact, with object keys in order. Tuples become arrays, and ints and
+//bools used as keys become strings
+pub fn stringify<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    let mut out = String::new();
+    write_json(&value, &mut out)?;
+    Ok(V::string(out))
+}
+
+fn write_json<V: Value>(value: &V, out: &mut String) -> Result<(), V> {
+    let unsupported = |v: &V| V::error(&format!("Can't convert {} to JSON", v.type_of()));
+    if value.is_null() {
+        out.push_str("null");
+    } else if value.as_bool().is_some() || value.as_int().is_some() {
+        out.push_str(&text(value));
+    } else if let Some(s) = value.as_str() {
+        write_string(s, out);
+    } else if let Some(elems) = value.as_array() {
+        write_elems(elems, out)?;
+    } else if let Some(elems) = value.as_tuple() {
+        write_elems(&elems, out)?;
+    } else if let Some(hash) = value.as_hash() {
+        let mut entries = Vec::new();
+        for (k, v) in hash {
+            let key = V::from_key(k.clone());
+            if key.as_str().is_none() && key.as_int().is_none() && key.as_bool().is_none() {
+                let msg = format!("Can't use {} as a JSON object key", key.type_of());
+                return Err(V::error(&msg));
+            }
+            entries.push((text(&key), v));
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        out.push('{');
+        for (ix, (k, v)) in entries.into_iter().enumerate() {
+            if ix > 0 {
+                out.push(',');
+            }
+            write_string(&k, out);
+            out.push(':');
+            write_json(v, out)?;
+        }
+        out.push('}');
+    } else {
+        return Err(unsupported(value));
+    }
+    Ok(())
+}
+
+fn write_elems<V: Value>(elems: &[V], out: &mut String) -> Result<(), V> {
+    out.push('[');
+    for (ix, elem) in elems.iter().enumerate() {
+        if ix > 0 {
+            out.push(',');
+        }
+        write_json(elem, out)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
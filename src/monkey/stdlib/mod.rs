@@ -0,0 +1,530 @@
+//Builtins shared by both engines, written once against `Value` which the
+//evaluator's and the VM's bindings implement
+mod arrays;
+mod functions;
+mod hashes;
+mod json;
+mod patterns;
+mod random;
+mod reflect;
+mod strings;
+mod time;
+
+pub use time::{Clock, FixedClock, SystemClock};
+
+use crate::monkey::rng::Rng;
+use std::{cmp::Ordering, collections::HashMap, fmt::Display, hash::Hash, str::FromStr};
+
+//`Display` is the `repr` of a value, with strings quoted
+pub trait Value: Clone + Sized + Display {
+    //the primitive form values take as hash keys
+    type Key: Clone + Eq + Hash;
+    fn null() -> Self;
+    fn int(i: i64) -> Self;
+    fn bool(b: bool) -> Self;
+    fn string(s: String) -> Self;
+    fn array(elems: Vec<Self>) -> Self;
+    fn tuple(elems: Vec<Self>) -> Self;
+    fn hash(entries: HashMap<Self::Key, Self>) -> Self;
+    fn error(msg: &str) -> Self;
+    fn is_null(&self) -> bool;
+    fn as_int(&self) -> Option<i64>;
+    fn as_bool(&self) -> Option<bool>;
+    fn as_str(&self) -> Option<&str>;
+    fn as_array(&self) -> Option<&[Self]>;
+    fn as_tuple(&self) -> Option<Vec<Self>>;
+    fn as_hash(&self) -> Option<&HashMap<Self::Key, Self>>;
+    //struct name and field values of a record
+    fn as_record(&self) -> Option<(&str, &[Self])>;
+    //`None` for values that can't be hash keys
+    fn to_key(&self) -> Option<Self::Key>;
+    fn from_key(key: Self::Key) -> Self;
+    //a record's type is the name of its struct
+    fn type_of(&self) -> String;
+    //`None` for values that can't be called, -1 when it takes any number.
+    //Parameters with a default count, a rest parameter makes it -1
+    fn arity(&self) -> Option<i64>;
+}
+
+//What natives need from the engine running them
+pub trait Context<V> {
+    //Calls a Monkey function, or any other callable, and gives back its value
+    fn apply(&mut self, f: V, args: Vec<V>) -> Result<V, V>;
+    fn print(&mut self, text: &str);
+    //read from the engine's `Clock`
+    fn now(&mut self) -> i64;
+    //runs `f` with the generator seeded for the run
+    fn rng<T>(&mut self, f: impl FnOnce(&mut Rng) -> T) -> T;
+}
+
+//How a value reads when printed or put in a string, strings as they are
+//and everything else like its `repr`
+pub fn text<V: Value>(value: &V) -> String {
+    match value.as_str() {
+        Some(s) => s.to_string(),
+        None => value.to_string(),
+    }
+}
+
+//Hash entries ordered by key, so output doesn't depend on hashing
+pub fn sorted_entries<V: Value>(hash: &HashMap<V::Key, V>) -> Vec<(V, V)> {
+    let mut entries = hash
+        .iter()
+        .map(|(k, v)| (V::from_key(k.clone()), v.clone()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| compare(a, b));
+    entries
+}
+
+//Natural order of values, used wherever builtins sort. Values of different
+//types order by type: null, bools, ints, strings, tuples, arrays, the rest
+pub fn compare<V: Value>(a: &V, b: &V) -> Ordering {
+    fn rank<V: Value>(v: &V) -> u8 {
+        if v.is_null() {
+            0
+        } else if v.as_bool().is_some() {
+            1
+        } else if v.as_int().is_some() {
+            2
+        } else if v.as_str().is_some() {
+            3
+        } else if v.as_tuple().is_some() {
+            4
+        } else if v.as_array().is_some() {
+            5
+        } else {
+            6
+        }
+    }
+    fn elems<V: Value>(a: &[V], b: &[V]) -> Ordering {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or(a.len().cmp(&b.len()))
+    }
+    if let (Some(a), Some(b)) = (a.as_bool(), b.as_bool()) {
+        return a.cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (a.as_int(), b.as_int()) {
+        return a.cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (a.as_str(), b.as_str()) {
+        return a.cmp(b);
+    }
+    if let (Some(a), Some(b)) = (a.as_tuple(), b.as_tuple()) {
+        return elems(&a, &b);
+    }
+    if let (Some(a), Some(b)) = (a.as_array(), b.as_array()) {
+        return elems(a, b);
+    }
+    rank(a).cmp(&rank(b))
+}
+
+//Structural equality, used where builtins look for values. Functions and
+//other values without structure are never equal
+pub fn equal<V: Value>(a: &V, b: &V) -> bool {
+    fn elems<V: Value>(a: &[V], b: &[V]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+    }
+    if let (Some(a), Some(b)) = (a.to_key(), b.to_key()) {
+        return a == b;
+    }
+    if let (Some(a), Some(b)) = (a.as_tuple(), b.as_tuple()) {
+        return elems(&a, &b);
+    }
+    if let (Some(a), Some(b)) = (a.as_array(), b.as_array()) {
+        return elems(a, b);
+    }
+    if let (Some((a_name, a)), Some((b_name, b))) = (a.as_record(), b.as_record()) {
+        return a_name == b_name && elems(a, b);
+    }
+    if let (Some(a), Some(b)) = (a.as_hash(), b.as_hash()) {
+        return a.len() == b.len()
+            && a.iter()
+                .all(|(k, v)| b.get(k).is_some_and(|other| equal(v, other)));
+    }
+    a.is_null() && b.is_null()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Native {
+    Split,
+    Join,
+    Trim,
+    Upper,
+    Lower,
+    Contains,
+    Replace,
+    StartsWith,
+    EndsWith,
+    IndexOf,
+    Chars,
+    Ord,
+    Chr,
+    Keys,
+    Values,
+    Entries,
+    Has,
+    Delete,
+    Merge,
+    Get,
+    Map,
+    Filter,
+    Reduce,
+    SortBy,
+    Find,
+    Any,
+    All,
+    Reverse,
+    Concat,
+    Slice,
+    Zip,
+    Flatten,
+    Unique,
+    Sum,
+    Range,
+    Sort,
+    TypeOf,
+    Str,
+    Int,
+    Arity,
+    Repr,
+    Inspect,
+    Print,
+    JsonParse,
+    JsonStringify,
+    Now,
+    FormatTime,
+    ParseTime,
+    Duration,
+    DurationIn,
+    RandomInt,
+    Shuffle,
+    Choice,
+    ReMatch,
+    ReFindAll,
+    ReReplace,
+    ReSplit,
+    RandomFloat,
+    First,
+    Last,
+    Rest,
+    Push,
+}
+
+impl Native {
+    //The VM refers to natives by their position here, new ones go at the end
+    //so compiled bytecode keeps working
+    const ALL: &'static [Native] = &[
+        Native::Split,
+        Native::Join,
+        Native::Trim,
+        Native::Upper,
+        Native::Lower,
+        Native::Contains,
+        Native::Replace,
+        Native::StartsWith,
+        Native::EndsWith,
+        Native::IndexOf,
+        Native::Chars,
+        Native::Ord,
+        Native::Chr,
+        Native::Keys,
+        Native::Values,
+        Native::Entries,
+        Native::Has,
+        Native::Delete,
+        Native::Merge,
+        Native::Get,
+        Native::Map,
+        Native::Filter,
+        Native::Reduce,
+        Native::SortBy,
+        Native::Find,
+        Native::Any,
+        Native::All,
+        Native::Reverse,
+        Native::Concat,
+        Native::Slice,
+        Native::Zip,
+        Native::Flatten,
+        Native::Unique,
+        Native::Sum,
+        Native::Range,
+        Native::Sort,
+        Native::TypeOf,
+        Native::Str,
+        Native::Int,
+        Native::Arity,
+        Native::Repr,
+        Native::Inspect,
+        Native::Print,
+        Native::JsonParse,
+        Native::JsonStringify,
+        Native::Now,
+        Native::FormatTime,
+        Native::ParseTime,
+        Native::Duration,
+        Native::DurationIn,
+        Native::RandomInt,
+        Native::Shuffle,
+        Native::Choice,
+        Native::ReMatch,
+        Native::ReFindAll,
+        Native::ReReplace,
+        Native::ReSplit,
+        Native::RandomFloat,
+        Native::First,
+        Native::Last,
+        Native::Rest,
+        Native::Push,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Native::Split => "split",
+            Native::Join => "join",
+            Native::Trim => "trim",
+            Native::Upper => "upper",
+            Native::Lower => "lower",
+            Native::Contains => "contains",
+            Native::Replace => "replace",
+            Native::StartsWith => "starts_with",
+            Native::EndsWith => "ends_with",
+            Native::IndexOf => "index_of",
+            Native::Chars => "chars",
+            Native::Ord => "ord",
+            Native::Chr => "chr",
+            Native::Keys => "keys",
+            Native::Values => "values",
+            Native::Entries => "entries",
+            Native::Has => "has",
+            Native::Delete => "delete",
+            Native::Merge => "merge",
+            Native::Get => "get",
+            Native::Map => "map",
+            Native::Filter => "filter",
+            Native::Reduce => "reduce",
+            Native::SortBy => "sort_by",
+            Native::Find => "find",
+            Native::Any => "any",
+            Native::All => "all",
+            Native::Reverse => "reverse",
+            Native::Concat => "concat",
+            Native::Slice => "slice",
+            Native::Zip => "zip",
+            Native::Flatten => "flatten",
+            Native::Unique => "unique",
+            Native::Sum => "sum",
+            Native::Range => "range",
+            Native::Sort => "sort",
+            Native::TypeOf => "type_of",
+            Native::Str => "str",
+            Native::Int => "int",
+            Native::Arity => "arity",
+            Native::Repr => "repr",
+            Native::Inspect => "inspect",
+            Native::Print => "print",
+            Native::JsonParse => "json_parse",
+            Native::JsonStringify => "json_stringify",
+            Native::Now => "now",
+            Native::FormatTime => "format_time",
+            Native::ParseTime => "parse_time",
+            Native::Duration => "duration",
+            Native::DurationIn => "duration_in",
+            Native::RandomInt => "random_int",
+            Native::Shuffle => "shuffle",
+            Native::Choice => "choice",
+            Native::ReMatch => "re_match",
+            Native::ReFindAll => "re_find_all",
+            Native::ReReplace => "re_replace",
+            Native::ReSplit => "re_split",
+            Native::RandomFloat => "random_float",
+            Native::First => "first",
+            Native::Last => "last",
+            Native::Rest => "rest",
+            Native::Push => "push",
+        }
+    }
+    //-1 for the ones taking a varying number of arguments
+    pub fn arity(self) -> i64 {
+        match self {
+            Native::Now | Native::RandomFloat => 0,
+            Native::Trim
+            | Native::Upper
+            | Native::Lower
+            | Native::Chars
+            | Native::Ord
+            | Native::Chr
+            | Native::Keys
+            | Native::Values
+            | Native::Entries
+            | Native::Reverse
+            | Native::Flatten
+            | Native::Unique
+            | Native::Sum
+            | Native::Sort
+            | Native::TypeOf
+            | Native::Str
+            | Native::Int
+            | Native::Arity
+            | Native::Repr
+            | Native::Inspect
+            | Native::JsonParse
+            | Native::JsonStringify
+            | Native::Shuffle
+            | Native::Choice
+            | Native::First
+            | Native::Last
+            | Native::Rest => 1,
+            Native::Split
+            | Native::Join
+            | Native::Contains
+            | Native::StartsWith
+            | Native::EndsWith
+            | Native::IndexOf
+            | Native::Has
+            | Native::Delete
+            | Native::Merge
+            | Native::Map
+            | Native::Filter
+            | Native::SortBy
+            | Native::Find
+            | Native::Any
+            | Native::All
+            | Native::Zip
+            | Native::FormatTime
+            | Native::Duration
+            | Native::DurationIn
+            | Native::RandomInt
+            | Native::ReMatch
+            | Native::ReFindAll
+            | Native::ReSplit
+            | Native::Push => 2,
+            Native::Replace | Native::Reduce | Native::ReReplace => 3,
+            Native::Get
+            | Native::Concat
+            | Native::Slice
+            | Native::Range
+            | Native::Print
+            | Native::ParseTime => -1,
+        }
+    }
+    pub fn index(self) -> u8 {
+        Self::ALL.iter().position(|n| *n == self).unwrap() as u8
+    }
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+    pub fn call<V: Value>(self, args: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+        let on_array = args.first().is_some_and(|a| a.as_array().is_some());
+        match self {
+            Native::Contains if on_array => arrays::contains(self, args),
+            Native::IndexOf if on_array => arrays::index_of(self, args),
+            Native::Split => strings::split(self, args),
+            Native::Join => strings::join(self, args),
+            Native::Trim => strings::map(self, args, |s| s.trim().to_string()),
+            Native::Upper => strings::map(self, args, str::to_uppercase),
+            Native::Lower => strings::map(self, args, str::to_lowercase),
+            Native::Contains => strings::contains(self, args),
+            Native::Replace => strings::replace(self, args),
+            Native::StartsWith => strings::test(self, args, |s, p| s.starts_with(p)),
+            Native::EndsWith => strings::test(self, args, |s, p| s.ends_with(p)),
+            Native::IndexOf => strings::index_of(self, args),
+            Native::Chars => strings::chars(self, args),
+            Native::Ord => strings::ord(self, args),
+            Native::Chr => strings::chr(self, args),
+            Native::Keys => hashes::entries(self, args, |k, _| k),
+            Native::Values => hashes::entries(self, args, |_, v| v),
+            Native::Entries => hashes::entries(self, args, |k, v| V::tuple(vec![k, v])),
+            Native::Has => hashes::has(self, args),
+            Native::Delete => hashes::delete(self, args),
+            Native::Merge => hashes::merge(self, args),
+            Native::Get => hashes::get(self, args),
+            Native::Map => functions::map(self, args, ctx),
+            Native::Filter => functions::filter(self, args, ctx),
+            Native::Reduce => functions::reduce(self, args, ctx),
+            Native::SortBy => functions::sort_by(self, args, ctx),
+            Native::Find => functions::find(self, args, ctx),
+            Native::Any => functions::quantify(self, args, ctx, false),
+            Native::All => functions::quantify(self, args, ctx, true),
+            Native::First => arrays::end(self, args, <[V]>::first),
+            Native::Last => arrays::end(self, args, <[V]>::last),
+            Native::Rest => arrays::rest(self, args),
+            Native::Push => arrays::push(self, args),
+            Native::Reverse => arrays::reverse(self, args),
+            Native::Concat => arrays::concat(self, args),
+            Native::Slice => arrays::slice(self, args),
+            Native::Zip => arrays::zip(self, args),
+            Native::Flatten => arrays::flatten(self, args),
+            Native::Unique => arrays::unique(self, args),
+            Native::Sum => arrays::sum(self, args),
+            Native::Range => arrays::range(self, args),
+            Native::Sort => arrays::sort(self, args),
+            Native::TypeOf => reflect::type_of(self, args),
+            Native::Str => reflect::str(self, args),
+            Native::Int => reflect::int(self, args),
+            Native::Arity => reflect::arity(self, args),
+            Native::Repr => reflect::repr(self, args),
+            Native::Inspect => reflect::inspect(self, args),
+            Native::Print => reflect::print(args, ctx),
+            Native::JsonParse => json::parse(self, args),
+            Native::JsonStringify => json::stringify(self, args),
+            Native::Now => time::now(self, args, ctx),
+            Native::FormatTime => time::format_time(self, args),
+            Native::ParseTime => time::parse_time(self, args),
+            Native::Duration => time::duration(self, args),
+            Native::DurationIn => time::duration_in(self, args),
+            Native::RandomInt => random::random_int(self, args, ctx),
+            Native::Shuffle => random::shuffle(self, args, ctx),
+            Native::Choice => random::choice(self, args, ctx),
+            Native::RandomFloat => random::random_float(self, args, ctx),
+            Native::ReMatch => patterns::re_match(self, args),
+            Native::ReFindAll => patterns::re_find_all(self, args),
+            Native::ReReplace => patterns::re_replace(self, args, ctx),
+            Native::ReSplit => patterns::re_split(self, args),
+        }
+    }
+}
+
+impl FromStr for Native {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|n| n.name() == s)
+            .copied()
+            .ok_or("Invalid builtin".to_string())
+    }
+}
+
+fn error<V: Value>(native: Native, expected: &str) -> V {
+    V::error(&format!(
+        "Expected {} for {} builtin",
+        expected,
+        native.name()
+    ))
+}
+
+//Checks the number of arguments, `expected` describes them for the error
+fn args<V: Value, const N: usize>(
+    native: Native,
+    args: Vec<V>,
+    expected: &str,
+) -> Result<[V; N], V> {
+    args.try_into().map_err(|_| error(native, expected))
+}
+
+fn array<V: Value>(native: Native, value: &V, expected: &str) -> Result<Vec<V>, V> {
+    value
+        .as_array()
+        .map(<[V]>::to_vec)
+        .ok_or_else(|| error(native, expected))
+}
+
+fn string<V: Value>(native: Native, value: &V, expected: &str) -> Result<String, V> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| error(native, expected))
+}
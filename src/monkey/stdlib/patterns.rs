@@ -0,0 +1,138 @@
+//Regex builtins. A match comes back as an array of the whole match followed
+//by each group, with null for groups that took no part in it
+use super::{args, error, string, Context, Native, Value};
+use crate::monkey::regex::Regex;
+
+fn regex<V: Value>(pattern: &str) -> Result<Regex, V> {
+    Regex::new(pattern).map_err(|e| V::error(&format!("Invalid regex '{}': {}", pattern, e)))
+}
+
+//Every match from left to right, after an empty match the search moves on
+//a char so it isn't found again
+fn matches(regex: &Regex, text: &[char]) -> Vec<Vec<Option<usize>>> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start <= text.len() {
+        let Some(caps) = regex.find_at(text, start) else {
+            break;
+        };
+        let (from, to) = (caps[0].unwrap(), caps[1].unwrap());
+        start = if from == to { to + 1 } else { to };
+        found.push(caps);
+    }
+    found
+}
+
+fn groups<V: Value>(text: &[char], caps: &[Option<usize>]) -> V {
+    let groups = caps.chunks(2).map(|span| match (span[0], span[1]) {
+        (Some(from), Some(to)) => V::string(text[from..to].iter().collect()),
+        _ => V::null(),
+    });
+    V::array(groups.collect())
+}
+
+//The pattern and the text, the first two arguments of every regex builtin
+fn pattern_text<V: Value>(native: Native, pattern: &V, s: &V) -> Result<(Regex, Vec<char>), V> {
+    let regex = regex(&string(native, pattern, "pattern and string")?)?;
+    let text = string(native, s, "pattern and string")?;
+    Ok((regex, text.chars().collect()))
+}
+
+//`re_match(pattern, s)` is the first match, null if there's none
+pub fn re_match<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [pattern, s] = args(native, a, "pattern and string")?;
+    let (regex, text) = pattern_text(native, &pattern, &s)?;
+    Ok(match regex.find_at(&text, 0) {
+        Some(caps) => groups(&text, &caps),
+        None => V::null(),
+    })
+}
+
+//The matched strings, or the full matches when the pattern has groups
+pub fn re_find_all<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [pattern, s] = args(native, a, "pattern and string")?;
+    let (regex, text) = pattern_text(native, &pattern, &s)?;
+    let found = matches(&regex, &text).into_iter().map(|caps| {
+        if regex.groups() == 0 {
+            V::string(text[caps[0].unwrap()..caps[1].unwrap()].iter().collect())
+        } else {
+            groups(&text, &caps)
+        }
+    });
+    Ok(V::array(found.collect()))
+}
+
+//`re_replace(pattern, s, replacement)` replaces every match. In a string
+//replacement `$1` stands for a group and `$$` for a dollar, a function
+//replacement gets the match and gives back the text to put in
+pub fn re_replace<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [pattern, s, replacement] = args(native, a, "pattern, string and replacement")?;
+    let (regex, text) = pattern_text(native, &pattern, &s)?;
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in matches(&regex, &text) {
+        let (from, to) = (caps[0].unwrap(), caps[1].unwrap());
+        out.extend(&text[last..from]);
+        match replacement.as_str() {
+            Some(template) => expand(template, &text, &caps, &mut out)?,
+            None if replacement.arity().is_some() => {
+                let value = ctx.apply(replacement.clone(), vec![groups(&text, &caps)])?;
+                out.push_str(&super::text(&value));
+            }
+            None => return Err(error(native, "pattern, string and replacement")),
+        }
+        last = to;
+    }
+    out.extend(&text[last..]);
+    Ok(V::string(out))
+}
+
+fn expand<V: Value>(
+    template: &str,
+    text: &[char],
+    caps: &[Option<usize>],
+    out: &mut String,
+) -> Result<(), V> {
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'$').is_some() {
+            out.push('$');
+            continue;
+        }
+        let mut digits = String::new();
+        while let Some(d) = chars.next_if(char::is_ascii_digit) {
+            digits.push(d);
+        }
+        if digits.is_empty() {
+            out.push('$');
+            continue;
+        }
+        let group = digits.parse::<usize>().ok().filter(|g| g * 2 < caps.len());
+        let Some(group) = group else {
+            return Err(V::error(&format!("No group {} in regex", digits)));
+        };
+        if let (Some(from), Some(to)) = (caps[group * 2], caps[group * 2 + 1]) {
+            out.extend(&text[from..to]);
+        }
+    }
+    Ok(())
+}
+
+//The pieces of the string between matches
+pub fn re_split<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [pattern, s] = args(native, a, "pattern and string")?;
+    let (regex, text) = pattern_text(native, &pattern, &s)?;
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for caps in matches(&regex, &text) {
+        let (from, to) = (caps[0].unwrap(), caps[1].unwrap());
+        pieces.push(V::string(text[last..from].iter().collect()));
+        last = to;
+    }
+    pieces.push(V::string(text[last..].iter().collect()));
+    Ok(V::array(pieces))
+}
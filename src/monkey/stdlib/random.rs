@@ -0,0 +1,51 @@
+//Random builtins, drawing from the engine's seeded generator so a run with
+//the same seed gives the same values in either engine
+use super::{args, array, error, Context, Native, Value};
+use crate::monkey::rng::Rng;
+
+//Draws of `random_float` are counted in these
+const FLOAT_SCALE: u128 = 1_000_000;
+
+//`random_int(lo, hi)`, both ends included
+pub fn random_int<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let expected = "two ints with the first not above the second";
+    let [lo, hi] = args(native, a, expected)?;
+    let (lo, hi) = match (lo.as_int(), hi.as_int()) {
+        (Some(lo), Some(hi)) if lo <= hi => (lo, hi),
+        _ => return Err(error(native, expected)),
+    };
+    let span = (hi as i128 - lo as i128 + 1) as u128;
+    let offset = ctx.rng(Rng::next_u64) as u128 % span;
+    Ok(V::int((lo as i128 + offset as i128) as i64))
+}
+
+pub fn shuffle<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "single array")?;
+    let mut elems = array(native, &arr, "single array")?;
+    for i in (1..elems.len()).rev() {
+        elems.swap(i, ctx.rng(|rng| rng.below(i + 1)));
+    }
+    Ok(V::array(elems))
+}
+
+pub fn choice<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [arr] = args(native, a, "non empty array")?;
+    let mut elems = array(native, &arr, "non empty array")?;
+    if elems.is_empty() {
+        return Err(error(native, "non empty array"));
+    }
+    let ix = ctx.rng(|rng| rng.below(elems.len()));
+    Ok(elems.swap_remove(ix))
+}
+
+//`random_float()`, a uniform draw from [0, 1). Monkey has no floats so it is
+//given in millionths, an int from 0 to 999999
+pub fn random_float<V: Value>(
+    native: Native,
+    a: Vec<V>,
+    ctx: &mut impl Context<V>,
+) -> Result<V, V> {
+    let [] = args(native, a, "no arguments")?;
+    let draw = (ctx.rng(Rng::next_u64) as u128 * FLOAT_SCALE) >> 64;
+    Ok(V::int(draw as i64))
+}
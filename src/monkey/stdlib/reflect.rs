@@ -0,0 +1,97 @@
+//Builtins asking what a value is or turning it into another type
+use super::{args, error, sorted_entries, text, Context, Native, Value};
+
+pub fn type_of<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    Ok(V::string(value.type_of()))
+}
+
+pub fn str<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    Ok(V::string(text(&value)))
+}
+
+//Parses strings, and turns bools into 1 or 0
+pub fn int<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    if let Some(i) = value.as_int() {
+        return Ok(V::int(i));
+    }
+    if let Some(b) = value.as_bool() {
+        return Ok(V::int(b as i64));
+    }
+    match value.as_str() {
+        Some(s) => s
+            .trim()
+            .parse()
+            .map(V::int)
+            .map_err(|_| V::error(&format!("Can't parse '{}' as an int", s))),
+        None => Err(error(native, "string, bool or int")),
+    }
+}
+
+pub fn arity<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [f] = args(native, a, "function")?;
+    f.arity()
+        .map(V::int)
+        .ok_or_else(|| error(native, "function"))
+}
+
+pub fn repr<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    Ok(V::string(value.to_string()))
+}
+
+//Like `repr` but collections holding other collections are spread over
+//several lines, one element per line
+pub fn inspect<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [value] = args(native, a, "single value")?;
+    let mut out = String::new();
+    write_nested(&value, 0, &mut out);
+    Ok(V::string(out))
+}
+
+fn write_nested<V: Value>(value: &V, indent: usize, out: &mut String) {
+    let (open, close, children) = if let Some(elems) = value.as_array() {
+        ("[", "]", elems.iter().map(|e| (None, e.clone())).collect())
+    } else if let Some(elems) = value.as_tuple() {
+        ("(", ")", elems.into_iter().map(|e| (None, e)).collect())
+    } else if let Some(hash) = value.as_hash() {
+        let entries = sorted_entries(hash);
+        (
+            "{",
+            "}",
+            entries.into_iter().map(|(k, v)| (Some(k), v)).collect(),
+        )
+    } else {
+        ("", "", Vec::<(Option<V>, V)>::new())
+    };
+    let nested = |v: &V| {
+        v.as_array().is_some_and(|a| !a.is_empty())
+            || v.as_tuple().is_some_and(|t| !t.is_empty())
+            || v.as_hash().is_some_and(|h| !h.is_empty())
+    };
+    if !children.iter().any(|(_, v)| nested(v)) {
+        out.push_str(&value.to_string());
+        return;
+    }
+    out.push_str(open);
+    out.push('\n');
+    for (key, child) in &children {
+        out.push_str(&" ".repeat(indent + 2));
+        if let Some(key) = key {
+            out.push_str(&format!("{}: ", key));
+        }
+        write_nested(child, indent + 2, out);
+        out.push_str(",\n");
+    }
+    out.push_str(&" ".repeat(indent));
+    out.push_str(close);
+}
+
+//Writes its arguments as text with spaces between them and no newline
+pub fn print<V: Value>(a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let parts = a.iter().map(text).collect::<Vec<_>>();
+    ctx.print(&parts.join(" "));
+    Ok(V::null())
+}
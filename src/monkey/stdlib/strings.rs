@@ -0,0 +1,102 @@
+//String builtins, positions are counted in chars like string indexing does
+use super::{args, error, string, Native, Value};
+
+//`split(s, sep)`, an empty separator splits into chars
+pub fn split<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let expected = "string and separator";
+    let [s, sep] = args(native, a, expected)?;
+    let (s, sep) = (
+        string(native, &s, expected)?,
+        string(native, &sep, expected)?,
+    );
+    let parts = if sep.is_empty() {
+        s.chars().map(|c| V::string(c.into())).collect()
+    } else {
+        s.split(sep.as_str()).map(|p| V::string(p.into())).collect()
+    };
+    Ok(V::array(parts))
+}
+
+//`join(strings, sep)`
+pub fn join<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [parts, sep] = args(native, a, "array of strings and separator")?;
+    let sep = string(native, &sep, "array of strings and separator")?;
+    let parts = parts
+        .as_array()
+        .ok_or_else(|| error(native, "array of strings and separator"))?
+        .iter()
+        .map(|p| string(native, p, "array of strings and separator"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(V::string(parts.join(&sep)))
+}
+
+//Builtins that turn one string into another
+pub fn map<V: Value>(native: Native, a: Vec<V>, f: impl Fn(&str) -> String) -> Result<V, V> {
+    let [s] = args(native, a, "single string")?;
+    Ok(V::string(f(&string(native, &s, "single string")?)))
+}
+
+//Builtins that compare a string with another
+pub fn test<V: Value>(native: Native, a: Vec<V>, f: impl Fn(&str, &str) -> bool) -> Result<V, V> {
+    let [s, other] = args(native, a, "two strings")?;
+    let (s, other) = (
+        string(native, &s, "two strings")?,
+        string(native, &other, "two strings")?,
+    );
+    Ok(V::bool(f(&s, &other)))
+}
+
+pub fn contains<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    test(native, a, |s, sub| s.contains(sub))
+}
+
+//`replace(s, from, to)` replaces every occurrence
+pub fn replace<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [s, from, to] = args(native, a, "three strings")?;
+    let s = string(native, &s, "three strings")?;
+    let from = string(native, &from, "three strings")?;
+    let to = string(native, &to, "three strings")?;
+    if from.is_empty() {
+        return Err(error(native, "non empty string to replace"));
+    }
+    Ok(V::string(s.replace(&from, &to)))
+}
+
+//Position of the first occurrence, -1 if there is none
+pub fn index_of<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [s, sub] = args(native, a, "two strings")?;
+    let (s, sub) = (
+        string(native, &s, "two strings")?,
+        string(native, &sub, "two strings")?,
+    );
+    Ok(V::int(match s.find(&sub) {
+        Some(byte) => s[..byte].chars().count() as i64,
+        None => -1,
+    }))
+}
+
+pub fn chars<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [s] = args(native, a, "single string")?;
+    let s = string(native, &s, "single string")?;
+    Ok(V::array(s.chars().map(|c| V::string(c.into())).collect()))
+}
+
+//Code point of a single char string
+pub fn ord<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [s] = args(native, a, "single char string")?;
+    let s = string(native, &s, "single char string")?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(V::int(c as i64)),
+        _ => Err(error(native, "single char string")),
+    }
+}
+
+pub fn chr<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [code] = args(native, a, "valid code point")?;
+    code.as_int()
+        .and_then(|i| u32::try_from(i).ok())
+        .and_then(char::from_u32)
+        .map(|c| V::string(c.into()))
+        .ok_or_else(|| error(native, "valid code point"))
+}
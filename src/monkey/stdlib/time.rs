@@ -0,0 +1,114 @@
+//Time builtins. Times are ints counting milliseconds since the Unix epoch,
+//durations are ints of milliseconds and calendar dates are always UTC
+use super::{args, error, string, Context, Native, Value};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, NaiveDate, NaiveDateTime, Utc,
+};
+use std::fmt::Debug;
+
+//Where `now` reads the time, hosts and tests can swap in a fixed one
+pub trait Clock: Debug {
+    //milliseconds since the Unix epoch
+    fn now(&self) -> i64;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+//Always the same time, so output that depends on it doesn't change
+#[derive(Debug)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
+
+pub fn now<V: Value>(native: Native, a: Vec<V>, ctx: &mut impl Context<V>) -> Result<V, V> {
+    let [] = args(native, a, "no arguments")?;
+    Ok(V::int(ctx.now()))
+}
+
+//`format_time(ts, fmt)` with strftime style formats like "%Y-%m-%d"
+pub fn format_time<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let expected = "time and format string";
+    let [ts, fmt] = args(native, a, expected)?;
+    let ts = ts.as_int().ok_or_else(|| error(native, expected))?;
+    let fmt = string(native, &fmt, expected)?;
+    let time = DateTime::from_timestamp_millis(ts).ok_or_else(|| error(native, "time in range"))?;
+    //chrono only reports a bad format when it's written out
+    let items = StrftimeItems::new(&fmt).collect::<Vec<_>>();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(error(native, "valid format string"));
+    }
+    Ok(V::string(time.format_with_items(items.iter()).to_string()))
+}
+
+//`parse_time(s)` reads RFC 3339, `parse_time(s, fmt)` a strftime format
+//which can leave out the time of day
+pub fn parse_time<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let expected = "string and optional format string";
+    let (s, fmt) = match &a[..] {
+        [s] => (string(native, s, expected)?, None),
+        [s, fmt] => (
+            string(native, s, expected)?,
+            Some(string(native, fmt, expected)?),
+        ),
+        _ => return Err(error(native, expected)),
+    };
+    let parsed = match fmt {
+        None => DateTime::parse_from_rfc3339(&s).map(|t| t.timestamp_millis()),
+        Some(fmt) => NaiveDateTime::parse_from_str(&s, &fmt)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(&s, &fmt).map(|d| d.and_time(Default::default()))
+            })
+            .map(|t| t.and_utc().timestamp_millis()),
+    };
+    parsed
+        .map(V::int)
+        .map_err(|_| V::error(&format!("Can't parse '{}' as a time", s)))
+}
+
+fn unit<V: Value>(native: Native, unit: &V) -> Result<i64, V> {
+    match unit.as_str() {
+        Some("milliseconds") => Ok(1),
+        Some("seconds") => Ok(1000),
+        Some("minutes") => Ok(60 * 1000),
+        Some("hours") => Ok(60 * 60 * 1000),
+        Some("days") => Ok(24 * 60 * 60 * 1000),
+        Some("weeks") => Ok(7 * 24 * 60 * 60 * 1000),
+        _ => Err(error(
+            native,
+            "milliseconds, seconds, minutes, hours, days or weeks",
+        )),
+    }
+}
+
+//`duration(2, "hours")` in milliseconds, to add to or take from a time
+pub fn duration<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [amount, u] = args(native, a, "int and unit")?;
+    let amount = amount
+        .as_int()
+        .ok_or_else(|| error(native, "int and unit"))?;
+    amount
+        .checked_mul(unit(native, &u)?)
+        .map(V::int)
+        .ok_or_else(|| V::error("Integer overflow in duration builtin"))
+}
+
+//`duration_in(ms, "hours")`, whole units only
+pub fn duration_in<V: Value>(native: Native, a: Vec<V>) -> Result<V, V> {
+    let [ms, u] = args(native, a, "duration and unit")?;
+    let ms = ms
+        .as_int()
+        .ok_or_else(|| error(native, "duration and unit"))?;
+    Ok(V::int(ms / unit(native, &u)?))
+}
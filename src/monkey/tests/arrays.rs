@@ -0,0 +1,91 @@
+use super::check;
+
+#[test]
+fn builtins_leave_the_array_alone() {
+    check(
+        "let a = [3, 1, 2]; puts(reverse(a), concat(a, [4], []), concat(), push(a, 9)); a",
+        "[2, 1, 3]\n[3, 1, 2, 4]\n[]\n[3, 1, 2, 9]\n[3, 1, 2]",
+    );
+}
+
+#[test]
+fn ends_of_arrays() {
+    check(
+        "let a = [3, 1, 2];
+        puts(first(a), last(a), rest(a), first([]), last([]), rest([]));
+        puts(try { first(5) } catch (e) { e });
+        puts(try { push(a) } catch (e) { e });
+        push(rest([1]), [2])",
+        "3\n2\n[1, 2]\nnull\nnull\n[]
+error: Expected single array for first builtin
+error: Expected array and value for push builtin
+[[2]]",
+    );
+}
+
+#[test]
+fn slice_builtin() {
+    check(
+        r#"let a = [3, 1, 2]; let none = find([], fn(x) { true });
+        puts(slice(a, 1), slice(a, -2, -1), slice("héllo", 1, 3));
+        slice(a, none, 2)"#,
+        "[1, 2]\n[1]\nél\n[3, 1]",
+    );
+}
+
+#[test]
+fn searching_compares_structurally() {
+    check(
+        r#"let a = [3, 1, 2]; let f = fn(x) { x };
+        puts(contains(a, 2), contains([[1], (1, 2)], (1, 2)), contains(a, "2"), contains("abc", "b"));
+        puts(index_of(a, 2), index_of(a, 9), index_of([{"a": 1}], {"a": 1}));
+        contains([f], f)"#,
+        "true\ntrue\nfalse\ntrue\n2\n-1\n0\nfalse",
+    );
+}
+
+#[test]
+fn reshaping() {
+    check(
+        r#"puts(zip([3, 1, 2], ["x", "y"]), flatten([[1, 2], 3, [[4]]]));
+        unique([1, 2, 1, [1], [1], "a", "a"])"#,
+        "[(3, 'x'), (1, 'y')]\n[1, 2, 3, [4]]\n[1, 2, [1], 'a']",
+    );
+}
+
+#[test]
+fn sum_and_range() {
+    check(
+        "puts(sum([3, 1, 2]), sum([])); (range(3), range(1, 4), range(5, 0, -2), range(3, 1))",
+        "6\n0\n([0, 1, 2], [1, 2, 3], [5, 3, 1], [])",
+    );
+}
+
+//Values of different types sort by type first
+#[test]
+fn natural_sort() {
+    check(
+        r#"puts(sort([3, "b", 1, true, find([], fn(x) { true }), "a", [2], (1, 2)]));
+        sort([[2, 1], [1, 5], [1]])"#,
+        "[null, true, 1, 3, 'a', 'b', (1, 2), [2]]\n[[1], [1, 5], [2, 1]]",
+    );
+}
+
+#[test]
+fn array_errors() {
+    check(
+        r#"let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { range(1, 2, 0) });
+        t(fn() { range(-9223372036854775807, 9223372036854775807, 2) });
+        t(fn() { sum([1, "a"]) });
+        t(fn() { slice(1, 2) });
+        t(fn() { zip([1]) });
+        concat([1], 2)"#,
+        "error: Expected one to three ints with a non zero step for range builtin
+error: Range of 9223372036854775807 elements is too large for range builtin, the limit is 10000000
+error: Expected array of ints for sum builtin
+error: Expected array or string and int bounds for slice builtin
+error: Expected two arrays for zip builtin
+Uncaught error: Expected arrays for concat builtin",
+    );
+}
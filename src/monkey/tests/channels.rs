@@ -0,0 +1,78 @@
+//Tasks and channels only exist in the vm
+use super::{eval, vm};
+
+#[test]
+fn tasks_take_turns_at_channels() {
+    let code = r#"let c = chan();
+        let done = chan();
+        let worker = fn(name, n) {
+            fn() {
+                let loop_ = fn(i) { if (i < n) { send(c, "${name} ${i}"); loop_(i + 1); } };
+                loop_(0);
+                send(done, name);
+            }
+        };
+        spawn(worker("a", 3));
+        spawn(worker("b", 3));
+        let drain = fn(k) { if (0 < k) { puts(recv(c)); drain(k - 1); } };
+        drain(6);
+        puts(recv(done));
+        recv(done)"#;
+    assert_eq!(vm(code), "a 0\nb 0\nb 1\na 1\nb 2\na 2\nb\n'a'");
+}
+
+#[test]
+fn busy_tasks_are_preempted() {
+    let code = r#"let busy = fn(i) { if (i < 300) { busy(i + 1) } else { i } };
+        spawn(fn() { puts("spinner ${busy(0)}"); });
+        spawn(fn() { puts("quick"); });
+        puts("main ${busy(0)}");
+        spawn(fn() { puts("after main"); });
+        "end""#;
+    let out = vm(code);
+    assert_eq!(out, "quick\nmain 300\nspinner 300\nafter main\n'end'");
+    //the same seed always gives the same interleaving
+    assert_eq!(vm(code), out);
+}
+
+#[test]
+fn spawn_arguments() {
+    assert_eq!(
+        vm("let r = chan(); spawn(fn(...xs) { send(r, len(xs)); }); recv(r)"),
+        "0"
+    );
+    assert_eq!(
+        vm("spawn(fn(x) { x })"),
+        "Uncaught error: Expected function without arguments for spawn"
+    );
+}
+
+#[test]
+fn deadlocks_are_thrown() {
+    assert_eq!(
+        vm("try { recv(chan()) } catch (e) { e }"),
+        "error: Deadlock, every task is waiting on a channel"
+    );
+    //tasks left waiting when main ends are dropped
+    assert_eq!(
+        vm(r#"spawn(fn() { recv(chan()); puts("never"); }); 1"#),
+        "1"
+    );
+}
+
+#[test]
+fn stack_overflow_in_a_task_is_catchable() {
+    assert_eq!(
+        vm("let deep = fn(n) { 1 + deep(n + 1) };
+            let r = chan();
+            spawn(fn() { send(r, try { deep(0) } catch (e) { e }); });
+            puts(recv(r));
+            try { deep(0) } catch (e) { e }"),
+        "error: Stack overflow\nerror: Stack overflow"
+    );
+}
+
+#[test]
+fn evaluator_has_no_channels() {
+    assert_eq!(eval("chan()"), "Uncaught error: Undefined variable chan");
+}
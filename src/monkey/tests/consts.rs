@@ -0,0 +1,70 @@
+use super::{check, eval, parse, vm};
+use crate::monkey::vm::{Compilation, CompiledContext};
+
+#[test]
+fn constants_read_like_variables() {
+    check(
+        r#"const LIMIT = 10 * 2 + 1;
+        const NAME = "cfg" + "-" + "v1";
+        const DOUBLE = LIMIT * 2;
+        let g = fn() { LIMIT + 1 };
+        puts(LIMIT, NAME, DOUBLE, g());
+        struct P { x, y }
+        const origin = P(0, 0);
+        origin.x"#,
+        "21\ncfg-v1\n42\n22\n0",
+    );
+}
+
+#[test]
+fn inner_scopes_can_shadow_constants() {
+    check(
+        "const DOUBLE = 42;
+        let f = fn(x) { const K = DOUBLE - 2; if (x > 0) { const K = 1; K + x } else { K } };
+        puts(f(5), f(0));
+        if (true) { let DOUBLE = 3; puts(DOUBLE); };
+        DOUBLE",
+        "6\n40\n3\n42",
+    );
+}
+
+//Both engines refuse to start the program, so nothing before the mistake
+//gets printed
+#[test]
+fn redefining_a_constant() {
+    let code = "const A = 1; puts(A); let A = 2;";
+    assert_eq!(
+        eval(code),
+        "Uncaught error: A is a constant and can't be defined again"
+    );
+    assert_eq!(
+        vm(code),
+        "Compile error: A is a constant and can't be defined again"
+    );
+}
+
+#[test]
+fn assigning_to_a_constant() {
+    let code = r#"struct P { x, y } const p = P(1, 2); puts("never"); let h = fn() { p.x = 5; };"#;
+    assert_eq!(
+        eval(code),
+        "Uncaught error: p is a constant and can't be assigned"
+    );
+    assert_eq!(
+        vm(code),
+        "Compile error: p is a constant and can't be assigned"
+    );
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    let mut ctx = CompiledContext::default();
+    parse("const LIMIT = 10 * 2 + 1; LIMIT * 2").compile(&mut ctx);
+    let constants = ctx
+        .get_constants()
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    assert!(constants.contains(&"21".to_string()), "{:?}", constants);
+    assert!(!constants.contains(&"10".to_string()), "{:?}", constants);
+}
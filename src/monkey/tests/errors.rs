@@ -0,0 +1,97 @@
+use super::check;
+
+#[test]
+fn thrown_values_are_caught() {
+    check(
+        r#"try { throw "boom"; 1 } catch (e) { "caught " + e }"#,
+        "'caught boom'",
+    );
+    check(r#"try { throw {"code": 7} } catch (e) { e["code"] }"#, "7");
+    check("try { 5 } catch (e) { 0 }", "5");
+}
+
+#[test]
+fn throws_unwind_calls() {
+    check(
+        "let f = fn(x) { if (x > 2) { throw x * 10 } else { f(x + 1) } }; try { f(0) } catch (e) { e + 1 }",
+        "31",
+    );
+    check(
+        r#"let down = fn(n) { if (n == 0) { throw "bottom" } else { 1 + down(n - 1) } };
+        try { down(20) } catch (e) { e }"#,
+        "'bottom'",
+    );
+    check(
+        "let mk = fn(n) { fn() { throw n } }; try { mk(9)() } catch (e) { e }",
+        "9",
+    );
+    check(
+        "let g = fn() { try { throw 1 } catch (e) { e + 100 } }; g() + 1",
+        "102",
+    );
+}
+
+#[test]
+fn rethrow_from_catch() {
+    check(
+        "try { try { throw 1 } catch (a) { throw a + 1 } } catch (b) { b }",
+        "2",
+    );
+}
+
+#[test]
+fn runtime_errors_are_catchable() {
+    check(
+        "let e = try { 1 / 0 } catch (e) { e }; puts(type_of(e)); e",
+        "error\nerror: Division by zero",
+    );
+    check(
+        "try { [1, 2][5] } catch (e) { e }",
+        "error: Index out of bounds for array",
+    );
+    check(
+        "try { len(1, 2) } catch (e) { e }",
+        "error: Expected single argument for len builtin",
+    );
+    //natives calling back into a closure pass its throw on
+    check(
+        "try { map([1, 2], fn(x) { throw x }) } catch (e) { e }",
+        "1",
+    );
+}
+
+#[test]
+fn uncaught_throws() {
+    check(
+        r#"puts("before"); throw [1, "a"]; puts("after")"#,
+        "before\nUncaught [1, 'a']",
+    );
+}
+
+#[test]
+fn operator_errors() {
+    check(
+        r#"let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { 1 + "a" });
+        t(fn() { true + true });
+        t(fn() { "ab" - 1 });
+        t(fn() { [1] - 1 });
+        t(fn() { (1, 2) - 1 });
+        t(fn() { {"a": 1} + "a" });
+        t(fn() { {"a": 1}["b"] });
+        t(fn() { -"a" });
+        t(fn() { !"a" });
+        t(fn() { 5() })"#,
+        "error: Invalid types for binary op
+error: Invalid op for bools
+error: Invalid op for string
+error: Invalid op for array
+error: Invalid op for tuple
+error: Invalid op for hash
+error: Invalid key for hash
+error: Expected int for - operator
+error: Expected int or bool for ! operator
+error: Expected closure or builtin for call
+null",
+    );
+}
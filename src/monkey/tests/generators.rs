@@ -0,0 +1,115 @@
+use super::{check, eval, parse_error};
+
+#[test]
+fn yield_values_lazily() {
+    check(
+        "let fibs = gen fn(a, b) { yield a; yield* fibs(b, a + b); };
+        let f = fibs(0, 1);
+        [next(f), next(f), next(f), next(f), next(f), next(f), next(f), next(f)]",
+        "[0, 1, 1, 2, 3, 5, 8, 13]",
+    );
+    check(
+        "let g = gen fn(x) { let y = x * 2; yield y; yield [x, y, y + 1]; };
+        let a = g(4);
+        let b = g(100);
+        puts(next(a), next(b), next(a), next(a));
+        (type_of(g), type_of(a))",
+        "8\n200\n[4, 8, 9]\nnull\n('function', 'generator')",
+    );
+}
+
+#[test]
+fn control_flow_inside_generators() {
+    check(
+        r#"let count = gen fn(n, limit) {
+            yield n;
+            if (n < limit) { yield n + 1; } else { yield 0; }
+            try {
+                yield "in try";
+                throw "boom";
+                yield "skipped";
+            } catch (e) {
+                yield "caught ${e}";
+            }
+            return 99;
+            yield "never";
+        };
+        let c = count(1, 5);
+        [next(c), next(c), next(c), next(c), next(c), next(c)]"#,
+        "[1, 2, 'in try', 'caught boom', null, null]",
+    );
+}
+
+#[test]
+fn throws_finish_the_generator() {
+    check(
+        r#"let bad = gen fn() { yield 1; throw "oops"; };
+        let b = bad();
+        puts(next(b));
+        puts(try { next(b) } catch (e) { e });
+        next(b)"#,
+        "1\noops\nnull",
+    );
+    check(
+        r#"let safe = gen fn() { try { yield* gen fn() { yield 1; throw "inner"; }(); } catch (e) { yield e; } };
+        let s = safe();
+        [next(s), next(s), next(s)]"#,
+        "[1, 'inner', null]",
+    );
+}
+
+#[test]
+fn delegation() {
+    check(
+        r#"let pair = gen fn() { yield "a"; yield "b"; };
+        let both = gen fn() { yield* pair(); yield "mid"; yield* pair(); };
+        let b = both();
+        [next(b), next(b), next(b), next(b), next(b), next(b)]"#,
+        "['a', 'b', 'mid', 'a', 'b', null]",
+    );
+    //a generator can be advanced both directly and through `yield*`
+    check(
+        r#"let inner = gen fn() { yield 1; yield 2; yield 3; };
+        let shared = inner();
+        let outer = gen fn() { yield* shared; yield "end"; };
+        let o = outer();
+        [next(o), next(shared), next(o), next(o), next(o)]"#,
+        "[1, 2, 3, 'end', null]",
+    );
+    check(
+        "let nat = gen fn(n) { yield n; yield* nat(n + 1); };
+        let g = nat(0);
+        let skip = fn(k) { if (k == 0) { 0 } else { next(g); skip(k - 1) } };
+        skip(200);
+        next(g)",
+        "200",
+    );
+}
+
+#[test]
+fn deep_delegation_in_the_evaluator() {
+    assert_eq!(
+        eval(
+            "let nat = gen fn(n) { yield n; yield* nat(n + 1); };
+            let g = nat(0);
+            let skip = fn(k) { if (k == 0) { 0 } else { next(g); skip(k - 1) } };
+            skip(30000);
+            next(g)"
+        ),
+        "30000"
+    );
+}
+
+#[test]
+fn generator_errors() {
+    check(
+        "let g = gen fn() { yield next(me); }; let me = g(); next(me)",
+        "Uncaught error: Generator is already running",
+    );
+    check(
+        "let x = gen fn() { yield* 5; }(); next(x)",
+        "Uncaught error: Expected generator for yield*",
+    );
+    assert!(parse_error("let f = fn() { yield 1; };"));
+    assert!(parse_error("gen fn() { let x = yield 1; };"));
+}
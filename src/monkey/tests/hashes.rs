@@ -0,0 +1,64 @@
+use super::check;
+
+//Keys come out sorted so both engines list them in the same order
+#[test]
+fn keys_values_and_entries() {
+    check(
+        r#"let h = {"b": 2, "a": 1, 3: "three", true: "yes"};
+        puts(keys(h), values(h));
+        entries({"x": 1, "y": 2})"#,
+        "[true, 3, 'a', 'b']\n['yes', 'three', 1, 2]\n[('x', 1), ('y', 2)]",
+    );
+}
+
+#[test]
+fn lookups() {
+    check(
+        r#"let h = {"a": 1, 3: "three"};
+        puts(has(h, "a"), has(h, "z"), get(h, "z"), get(h, "z", 0));
+        get(h, 3, 0)"#,
+        "true\nfalse\nnull\n0\n'three'",
+    );
+}
+
+#[test]
+fn updates_return_new_hashes() {
+    check(
+        r#"let h = {"a": 1, "b": 2};
+        let d = delete(h, "a");
+        puts(len(d), len(h), len({}));
+        puts(keys(merge(h, {"b": 20, "c": 3})));
+        get(merge(h, {"b": 20}), "b")"#,
+        "1\n2\n0\n['a', 'b', 'c']\n20",
+    );
+}
+
+#[test]
+fn word_frequencies() {
+    check(
+        r#"let count = fn(ws, acc) {
+            if (len(ws) == 0) { acc } else {
+                let w = first(ws);
+                count(rest(ws), merge(acc, {w: get(acc, w, 0) + 1}))
+            }
+        };
+        entries(count(split("a b a c b a", " "), {}))"#,
+        "[('a', 3), ('b', 2), ('c', 1)]",
+    );
+}
+
+#[test]
+fn hash_errors() {
+    check(
+        r#"let h = {"a": 1};
+        let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { keys([1]) });
+        t(fn() { has(h, [1]) });
+        t(fn() { get(h) });
+        merge(h, 1)"#,
+        "error: Expected single hash for keys builtin
+error: Expected hash and key for has builtin
+error: Expected hash, key and optional default for get builtin
+Uncaught error: Expected two hashes for merge builtin",
+    );
+}
@@ -0,0 +1,80 @@
+use super::{check, vm};
+
+#[test]
+fn map_filter_reduce() {
+    check(
+        "let a = [5, 3, 8, 1];
+        puts(map(a, fn(x) { x * 2 }), filter(a, fn(x) { x > 2 }));
+        reduce(a, 0, fn(acc, x) { acc + x })",
+        "[10, 6, 16, 2]\n[5, 3, 8]\n17",
+    );
+    check(
+        r#"let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } };
+        puts(map(["a", "b"], upper), map([3, 4, 5], fact));
+        map([[1, 2], [3]], fn(xs) { map(xs, fn(x) { x + 1 }) })"#,
+        "['A', 'B']\n[6, 24, 120]\n[[2, 3], [4]]",
+    );
+}
+
+#[test]
+fn sort_and_search() {
+    //sorting is stable
+    check(
+        r#"let a = [5, 3, 8, 1];
+        puts(sort_by(a, fn(x, y) { x - y }), sort_by([(2, "b"), (1, "a"), (2, "a")], fn(x, y) { x[0] - y[0] }));
+        puts(find(a, fn(x) { x > 4 }), find(a, fn(x) { x > 10 }));
+        (any(a, fn(x) { x == 8 }), all(a, fn(x) { x > 1 }), all([], fn(x) { false }))"#,
+        "[1, 3, 5, 8]\n[(1, 'a'), (2, 'b'), (2, 'a')]\n5\nnull\n(true, false, true)",
+    );
+}
+
+#[test]
+fn deep_recursion_inside_callbacks() {
+    check(
+        "let count = fn(n) { if (n == 0) { 0 } else { count(n - 1) } }; map([10000], count)",
+        "[0]",
+    );
+}
+
+#[test]
+fn throws_inside_callbacks() {
+    check(
+        r#"let a = [5, 3, 8, 1];
+        try { map(a, fn(x) { if (x == 8) { throw "boom" }; x }) } catch (e) { puts("caught", e); };
+        map(a, fn(x) { try { if (x == 3) { throw x }; x } catch (e) { -e } })"#,
+        "caught\nboom\n[5, -3, 8, 1]",
+    );
+}
+
+#[test]
+fn generators_inside_callbacks() {
+    check(
+        "let g2 = gen fn() { yield 1; yield 2; };
+        let g = g2();
+        puts(map([0, 0, 0], fn(x) { next(g) }));
+        map([1, 2], fn(x) { let s = g2(); next(s) + x })",
+        "[1, 2, null]\n[2, 3]",
+    );
+}
+
+#[test]
+fn callback_errors() {
+    check(
+        "let a = [5, 3, 8, 1];
+        let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { filter(a, fn(x) { x }) });
+        t(fn() { map(a, fn(x, y) { x }) });
+        sort_by(a, fn(x, y) { true })",
+        "error: Expected function returning bool for filter builtin
+error: Expected 2 args for call but got 1
+Uncaught error: Expected function returning int for sort_by builtin",
+    );
+}
+
+#[test]
+fn callbacks_cannot_wait_on_channels() {
+    assert_eq!(
+        vm("let c = chan(); spawn(fn() { send(c, 1); }); map([10], fn(x) { x + recv(c) })"),
+        "Uncaught error: Can't wait on a channel inside a builtin callback"
+    );
+}
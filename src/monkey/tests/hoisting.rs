@@ -0,0 +1,48 @@
+use super::{check, eval, vm};
+
+#[test]
+fn mutual_recursion_at_top_level() {
+    check(
+        "let is_even = fn(n) { if (n == 0) { true } else { is_odd(n - 1) } };
+        let is_odd = fn(n) { if (n == 0) { false } else { is_even(n - 1) } };
+        puts(is_even(10));
+        is_odd(7)",
+        "true\ntrue",
+    );
+}
+
+#[test]
+fn functions_see_later_definitions() {
+    check(
+        "let early = fn() { later + 1 }; let later = 41; early()",
+        "42",
+    );
+    check(
+        "let make = fn() { Pt(1, 2) }; struct Pt { a, b } make()",
+        "Pt { a: 1, b: 2 }",
+    );
+    check("let f = fn() { q }; let (q, r) = (5, 6); f()", "5");
+    //a global defined again is the same variable
+    check("let x = 1; let get_x = fn() { x }; let x = 2; get_x()", "2");
+}
+
+#[test]
+fn reads_before_definition() {
+    let code = "let early = fn() { later + 1 }; early(); let later = 41;";
+    check(code, "Uncaught error: later is used before it is defined");
+    check(
+        "struct P { x } let f = fn() { p.x = 1 }; f(); let p = P(0);",
+        "Uncaught error: p is used before it is defined",
+    );
+}
+
+#[test]
+fn locals_are_not_hoisted() {
+    let code = "let f = fn() { let g = fn() { h() }; let h = fn() { 7 }; g() }; f()";
+    assert_eq!(eval(code), "7");
+    assert_eq!(vm(code), "Compile error: Undefined variable h");
+    assert_eq!(
+        vm("undefined_thing"),
+        "Compile error: Undefined variable undefined_thing"
+    );
+}
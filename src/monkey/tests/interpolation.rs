@@ -0,0 +1,39 @@
+use super::{check, eval, parse_error, vm};
+
+#[test]
+fn interpolated_expressions() {
+    check(
+        r#"let name = "Bob"; let age = 41; "Hello ${name}, you are ${age + 1}""#,
+        "'Hello Bob, you are 42'",
+    );
+    //anything but a string is put in as it prints
+    check(
+        r#""${[1, "a"]} ${(1, 2)} ${true} ${{"k": "v"}}""#,
+        "'[1, 'a'] (1, 2) true { 'k': 'v' }'",
+    );
+}
+
+#[test]
+fn dollar_without_braces_is_text() {
+    check(r#""cost $5 ${"x" + "y"}""#, "'cost $5 xy'");
+    check(r#""escaped \${name}""#, "'escaped ${name}'");
+}
+
+#[test]
+fn nested_interpolation() {
+    check(
+        r#"let n = "Bob"; "nested ${"inner ${n}"}!""#,
+        "'nested inner Bob!'",
+    );
+}
+
+#[test]
+fn interpolation_errors() {
+    //names are looked up when the evaluator gets to them, the vm resolves
+    //them while compiling
+    let code = r#"try { "${undefined_name}" } catch (e) { e }"#;
+    assert_eq!(eval(code), "error: Undefined variable undefined_name");
+    assert_eq!(vm(code), "Compile error: Undefined variable undefined_name");
+    assert!(parse_error(r#""${1 +}""#));
+    assert!(parse_error(r#""${1 2}""#));
+}
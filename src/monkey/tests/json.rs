@@ -0,0 +1,75 @@
+use super::check;
+
+#[test]
+fn parse_documents() {
+    check(
+        r#"let doc = json_parse("{\"name\": \"Bob\", \"tags\": [\"a\", \"b\"], \"age\": 42, \"ok\": true, \"none\": null, \"nested\": {\"x\": -1}}");
+        puts(doc);
+        (doc["tags"][1], doc["age"] + 1, type_of(doc["none"]))"#,
+        "{ 'age': 42, 'name': 'Bob', 'nested': { 'x': -1 }, 'none': null, 'ok': true, 'tags': ['a', 'b'] }
+('b', 43, 'null')",
+    );
+    check(
+        r#"puts(json_parse("  [1, 2 , 3 ]  ")); json_parse("\"\\u00e9\\ud83d\\ude00!\"")"#,
+        "[1, 2, 3]\n'é😀!'",
+    );
+}
+
+//Keys are written sorted so the output doesn't depend on hash order
+#[test]
+fn stringify_values() {
+    check(
+        r#"puts(json_stringify({"b": [false], "a": {"x": -1}, 1: true, "none": json_parse("null")}));
+        json_stringify([1, "a\"b\\c\n", (1, 2), {}, [], 9223372036854775807])"#,
+        r#"{"1":true,"a":{"x":-1},"b":[false],"none":null}
+'[1,"a\"b\\c\n",[1,2],{},[],9223372036854775807]'"#,
+    );
+}
+
+#[test]
+fn round_trip() {
+    check(
+        r#"let v = {"k": "v\tw", "list": [1, [2, {"deep": true}]]};
+        json_stringify(json_parse(json_stringify(v))) == json_stringify(v)"#,
+        "true",
+    );
+}
+
+#[test]
+fn parse_errors() {
+    check(
+        r#"let t = fn(s) { try { json_parse(s) } catch (e) { puts(e) } };
+        t("[1, 2");
+        t("1.5");
+        t("{\"a\" 1}");
+        t("tru");
+        t("[1] x");
+        t("99999999999999999999");
+        0"#,
+        "error: Invalid JSON, expected ',' or ']' at 5
+error: Invalid JSON, expected an integer at 0
+error: Invalid JSON, expected ':' at 5
+error: Invalid JSON, expected a value at 0
+error: Invalid JSON, expected end of input at 4
+error: Invalid JSON, expected an integer that fits in 64 bits at 0
+0",
+    );
+}
+
+#[test]
+fn stringify_errors() {
+    check(
+        "struct P { x }
+        let t = fn(v) { try { json_stringify(v) } catch (e) { puts(e) } };
+        t([fn(x) { x }]);
+        t(len);
+        t(P(1));
+        t({(1, 2): 3});
+        0",
+        "error: Can't convert function to JSON
+error: Can't convert builtin to JSON
+error: Can't convert P to JSON
+error: Can't use tuple as a JSON object key
+0",
+    );
+}
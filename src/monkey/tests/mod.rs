@@ -0,0 +1,93 @@
+//Programs are run through both engines, which have to agree on what gets
+//printed and on the value or error the program ends with
+use super::{
+    evaluator::{Env, Evaluation},
+    vm::{Compilation, CompiledContext, Machine},
+    FixedClock, Lexer, Parser, Program,
+};
+use std::rc::Rc;
+
+mod arrays;
+mod channels;
+mod consts;
+mod errors;
+mod generators;
+mod hashes;
+mod higher_order;
+mod hoisting;
+mod interpolation;
+mod json;
+mod named_fns;
+mod operators;
+mod params;
+mod prelude;
+mod random;
+mod records;
+mod reflection;
+mod regex;
+mod scoping;
+mod slicing;
+mod spread;
+mod strings;
+mod tail_calls;
+mod time;
+mod tuples;
+
+const NOW: i64 = 1_700_000_000_123;
+const SEED: u64 = 42;
+
+fn parse(code: &str) -> Program {
+    Parser::new(Lexer::new(code.bytes())).program().unwrap()
+}
+
+fn parse_error(code: &str) -> bool {
+    Parser::new(Lexer::new(code.bytes())).program().is_err()
+}
+
+fn eval(code: &str) -> String {
+    let env = Rc::new(
+        Env::default()
+            .with_seed(SEED)
+            .with_clock(Rc::new(FixedClock(NOW))),
+    );
+    env.load_prelude();
+    let result = parse(code).eval(&env);
+    let out = env.stdout.borrow().clone();
+    match result {
+        Ok(value) => format!("{}{}", out, value),
+        Err(thrown) => format!("{}Uncaught {}", out, thrown),
+    }
+}
+
+fn vm(code: &str) -> String {
+    let mut ctx = CompiledContext::default();
+    let mut machine = Machine::with_seed(SEED).with_clock(Rc::new(FixedClock(NOW)));
+    machine.load_prelude(&mut ctx);
+    parse(code).compile(&mut ctx);
+    let errors = ctx.take_errors();
+    if !errors.is_empty() {
+        return errors
+            .iter()
+            .map(|e| format!("Compile error: {}", e))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    ctx.remove_last_pop();
+    let result = machine.run(ctx.get_constants(), ctx.make_main_closure());
+    let out = machine.get_stdout();
+    match result {
+        Ok(()) => format!("{}{}", out, machine.get_last_expr()),
+        Err(thrown) => format!("{}Uncaught {}", out, thrown),
+    }
+}
+
+//What both engines give for `code`
+fn run(code: &str) -> String {
+    let (evaluated, compiled) = (eval(code), vm(code));
+    assert_eq!(evaluated, compiled, "engines disagree on:\n{}", code);
+    evaluated
+}
+
+fn check(code: &str, expected: &str) {
+    assert_eq!(run(code), expected, "for:\n{}", code);
+}
@@ -0,0 +1,80 @@
+use super::check;
+
+#[test]
+fn declarations() {
+    check(
+        "fn fact(n) { if (n < 2) { 1 } else { n * fact(n - 1) } } fact(10)",
+        "3628800",
+    );
+    check(
+        "fn even(n) { if (n == 0) { true } else { odd(n - 1) } }
+        fn odd(n) { if (n == 0) { false } else { even(n - 1) } }
+        puts(even(10));
+        odd(7)",
+        "true\ntrue",
+    );
+}
+
+#[test]
+fn recursive_helpers_inside_functions() {
+    check(
+        "let map = fn(arr, f) {
+            let iter = fn(arr, acc) {
+                if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) }
+            };
+            iter(arr, [])
+        };
+        map([1, 2, 3], fn(x) { x * 2 })",
+        "[2, 4, 6]",
+    );
+    //a helper that captures free variables and is called from a closure
+    check(
+        "let outer = fn(n) {
+            let count = fn(i) {
+                let step = fn() { if (i < n) { count(i + 1) } else { i } };
+                step()
+            };
+            count(0)
+        };
+        outer(5)",
+        "5",
+    );
+    check(
+        "let sum = fn(xs) {
+            let base = 100;
+            fn go(xs, acc) { if (len(xs) == 0) { acc + base } else { go(rest(xs), acc + first(xs)) } }
+            go(xs, 0)
+        };
+        sum([1, 2, 3])",
+        "106",
+    );
+}
+
+#[test]
+fn named_function_literals() {
+    check(
+        r#"let alias = fn loop(n) { if (n == 0) { "done" } else { loop(n - 1) } }; alias(3)"#,
+        "'done'",
+    );
+    check("let shadow = fn(shadow) { shadow }; shadow(9)", "9");
+    check("fn(x) { x + 1 }(1)", "2");
+}
+
+#[test]
+fn self_reference_survives_rebinding() {
+    check(
+        r#"fn f(n) { if (n == 0) { "bottom" } else { f(n - 1) } }
+        let g = f;
+        let f = 5;
+        puts(f);
+        g(3)"#,
+        "5\n'bottom'",
+    );
+    check(
+        r#"let h = fn(n) { if (n == 0) { "bottom" } else { h(n - 1) } };
+        let k = h;
+        let h = 5;
+        k(3)"#,
+        "'bottom'",
+    );
+}
@@ -0,0 +1,60 @@
+use super::{check, parse_error};
+
+#[test]
+fn builtin_precedence() {
+    check(
+        "puts(1 + 2 * 3 - 4 / 2, (1 + 2) * 3, 7 % 3, 1 < 2, 2 > 1, 1 != 1, !true); -(-3)",
+        "5\n9\n1\ntrue\ntrue\nfalse\nfalse\n3",
+    );
+    check("let x = -1; puts(3--x); 2*-3", "2\n-6");
+}
+
+#[test]
+fn declared_operators() {
+    check(
+        "infixl 6 <+> = fn(a, b) { [a[0] + b[0], a[1] + b[1]] }; [1, 2] <+> [3, 4] <+> [10, 20]",
+        "[14, 26]",
+    );
+    check(
+        r#"infixr 2 <> = fn(a, b) { a + b }; "a" <> "b" <> "c""#,
+        "'abc'",
+    );
+    check(
+        "infixl 1 |> = fn(x, f) { f(x) }; 3 |> fn(x) { x * 10 } |> fn(x) { x + 1 }",
+        "31",
+    );
+}
+
+#[test]
+fn associativity_and_precedence() {
+    check(
+        "infixr 8 ^^ = fn(a, b) { if (b == 0) { 1 } else { a * (a ^^ (b - 1)) } };
+        puts(2 ^^ 3 ^^ 2);
+        1 + 2 ^^ 3 * 2",
+        "512\n17",
+    );
+}
+
+#[test]
+fn operators_declared_inside_functions() {
+    check(
+        "let f = fn() { infixl 7 %% = fn(a, b) { a % b + 100 }; 7 %% 4 }; f()",
+        "103",
+    );
+}
+
+#[test]
+fn declared_operators_in_interpolation() {
+    check(
+        r#"infixl 6 <+> = fn(a, b) { [a[0] + b[0], a[1] + b[1]] };
+        infixr 8 ^^ = fn(a, b) { if (b == 0) { 1 } else { a * (a ^^ (b - 1)) } };
+        "sum ${[1, 2] <+> [3, 4]} pow ${2 ^^ 10}""#,
+        "'sum [4, 6] pow 1024'",
+    );
+}
+
+#[test]
+fn undeclared_operators() {
+    assert!(parse_error("1 <+> 2"));
+    assert!(parse_error(r#""${1 <+> 2}""#));
+}
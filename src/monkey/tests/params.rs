@@ -0,0 +1,44 @@
+use super::check;
+
+#[test]
+fn default_params() {
+    check(
+        "let f = fn(a, b = 2) { a + b }; puts(f(1)); f(1, 5)",
+        "3\n6",
+    );
+    //defaults are evaluated on every call and can use earlier params
+    check(
+        "let f = fn(a, b = a * 10, c = [b]) { c }; puts(f(1)); f(1, 2, 3)",
+        "[10]\n3",
+    );
+}
+
+#[test]
+fn rest_params() {
+    check(
+        "let f = fn(first, ...rest) { rest }; puts(f(1)); puts(f(1, 2, 3)); len(f(1, 2))",
+        "[]\n[2, 3]\n1",
+    );
+    check(
+        "let f = fn(a, b = 5, ...more) { [a, b, more] }; puts(f(1)); f(1, 2, 3, 4)",
+        "[1, 5, []]\n[1, 2, [3, 4]]",
+    );
+}
+
+#[test]
+fn rest_param_shadows_builtin() {
+    check("let tail = fn(x, ...rest) { rest }; tail(1, 2)", "[2]");
+    check("let first = fn(a) { \"mine\" }; first([1])", "'mine'");
+}
+
+#[test]
+fn wrong_number_of_args() {
+    check(
+        "let f = fn(a, b = 1) { a }; try { f() } catch (e) { e }",
+        "error: Expected 2 args for call but got 0",
+    );
+    check(
+        "let f = fn(a, b = 1) { a }; try { f(1, 2, 3) } catch (e) { e }",
+        "error: Expected 2 args for call but got 3",
+    );
+}
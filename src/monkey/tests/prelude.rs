@@ -0,0 +1,46 @@
+use super::check;
+
+#[test]
+fn helpers() {
+    check(
+        r#"let out = each([1, 2, 3], fn(x) { puts("item ${x}") });
+        puts(out, identity(5));
+        let inc = fn(x) { x + 1 };
+        let dbl = fn(x) { x * 2 };
+        puts(compose(inc, dbl)(5), pipe(inc, dbl)(5), compose()(7));
+        let add3 = fn(a, b, c) { a + b + c };
+        (partial(add3, 1, 2)(3), partial(add3)(1, 2, 3))"#,
+        "item 1\nitem 2\nitem 3\n[1, 2, 3]\n5\n11\n12\n7\n(6, 6)",
+    );
+}
+
+#[test]
+fn collection_helpers() {
+    check(
+        r#"puts(flat_map([1, 2, 3], fn(x) { [x, x * 10] }), count([1, 2, 3, 4, 5], fn(x) { x % 2 == 1 }));
+        puts(group_by(["apple", "avocado", "banana", "cherry", "blue"], fn(s) { first(chars(s)) }));
+        times(4, fn(i) { i * i })"#,
+        "[1, 10, 2, 20, 3, 30]\n3\n{ 'a': ['apple', 'avocado'], 'b': ['banana', 'blue'], 'c': ['cherry'] }\n[0, 1, 4, 9]",
+    );
+}
+
+#[test]
+fn user_code_can_redefine_helpers() {
+    check(
+        r#"let each = fn(x) { "shadowed ${x}" }; each(1)"#,
+        "'shadowed 1'",
+    );
+}
+
+//Helpers keep calling the builtins, whatever user code names its own functions
+#[test]
+fn helpers_ignore_user_definitions() {
+    check(
+        r#"let filter = fn(arr, pred) { [99] };
+        let map = fn(a, f) { "mine" };
+        let reduce = fn(a, b, c) { "r" };
+        puts(count([1, 2, 3], fn(x) { true }), times(2, fn(i) { i }));
+        compose(fn(x) { x + 1 })(1)"#,
+        "3\n[0, 1]\n2",
+    );
+}
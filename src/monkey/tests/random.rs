@@ -0,0 +1,55 @@
+use super::{check, parse};
+use crate::monkey::evaluator::{Env, Evaluation};
+use std::rc::Rc;
+
+//The draws below are what seed 42 gives, the same in both engines
+#[test]
+fn seeded_ints() {
+    check(
+        "map(range(8), fn(i) { random_int(1, 6) })",
+        "[1, 3, 3, 2, 3, 6, 1, 6]",
+    );
+    check(
+        "(random_int(-9223372036854775807 - 1, 9223372036854775807) != 0, random_int(5, 5))",
+        "(true, 5)",
+    );
+}
+
+#[test]
+fn seeded_floats() {
+    //millionths, as Monkey has no floats
+    check(
+        "map(range(4), fn(i) { random_float() })",
+        "[339085, 782255, 790137, 944042]",
+    );
+}
+
+#[test]
+fn seeded_shuffle_and_choice() {
+    check(
+        r#"(shuffle(range(10)), choice(["a", "b", "c"]), choice([1]))"#,
+        "([1, 4, 3, 8, 9, 2, 7, 6, 5, 0], 'a', 1)",
+    );
+}
+
+#[test]
+fn seeds_change_the_draws() {
+    let draws = |seed| {
+        let env = Rc::new(Env::default().with_seed(seed));
+        parse("map(range(8), fn(i) { random_int(1, 1000) })")
+            .eval(&env)
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(draws(7), draws(7));
+    assert_ne!(draws(7), draws(8));
+}
+
+#[test]
+fn random_errors() {
+    check(
+        "puts(try { random_int(3, 1) } catch (e) { e }); choice([])",
+        "error: Expected two ints with the first not above the second for random_int builtin
+Uncaught error: Expected non empty array for choice builtin",
+    );
+}
@@ -0,0 +1,112 @@
+use super::{check, eval, parse, vm};
+use crate::monkey::vm::{Compilation, CompiledContext};
+
+#[test]
+fn construct_and_read_fields() {
+    check(
+        "struct Point { x, y } let p = Point(1, 2); puts(p, p.x + p.y, type_of(p)); type_of(Point)",
+        "Point { x: 1, y: 2 }\n3\nPoint\n'struct'",
+    );
+}
+
+#[test]
+fn assign_fields() {
+    check(
+        "struct Point { x, y } struct Line { from, to }
+        let p = Point(1, 2);
+        p.x = 10;
+        let l = Line(p, Point(3, 4));
+        l.to.y = 40;
+        puts(p, l.to.y);
+        l",
+        "Point { x: 10, y: 2 }\n40\nLine { from: Point { x: 10, y: 2 }, to: Point { x: 3, y: 40 } }",
+    );
+    //records are values, a function updating its argument updates a copy
+    check(
+        "struct Point { x, y } let p = Point(1, 2);
+        let move = fn(pt, dx) { pt.x = pt.x + dx; pt };
+        puts(move(p, 5));
+        p.x",
+        "Point { x: 6, y: 2 }\n1",
+    );
+}
+
+#[test]
+fn compare_records() {
+    check(
+        "struct Point { x, y } struct Other { x, y }
+        puts(Point(1, 2) == Point(1, 2), Point(1, 2) != Point(1, 3), Point(1, 2) == Other(1, 2));
+        Point(Point(1, 2), [3]) == Point(Point(1, 2), [3])",
+        "true\ntrue\nfalse\ntrue",
+    );
+    check(
+        "struct Point { x, y } Point(1, 2) < Point(1, 3)",
+        "Uncaught error: Expected == or != to compare records",
+    );
+}
+
+#[test]
+fn record_errors() {
+    check(
+        "struct Point { x, y } let p = Point(1, 2);
+        puts(try { p.z } catch (e) { e });
+        puts(try { p.z = 1 } catch (e) { e });
+        puts(try { Point(1) } catch (e) { e });
+        5.x",
+        "error: Unknown field for record
+error: Unknown field for record
+error: Wrong number of fields for struct constructor
+Uncaught error: Expected record for field access",
+    );
+}
+
+#[test]
+fn field_names_share_a_constant() {
+    let mut ctx = CompiledContext::default();
+    parse("struct Point { x, y } let p = Point(1, 2); p.x = p.x + p.y; p.x").compile(&mut ctx);
+    let names = ctx
+        .get_constants()
+        .iter()
+        .filter(|c| c.to_string() == "'x'")
+        .count();
+    assert_eq!(names, 1);
+}
+
+//Closures in the VM hold copies of what they capture, so both engines only
+//let a function assign its own variables and the program's
+#[test]
+fn assigning_captured_variables() {
+    let code =
+        "let g = fn() { struct P { x } let p = P(1); let f = fn() { p.x = 5 }; f(); p.x }; g()";
+    let message = "p is defined outside this function and can't be assigned";
+    assert_eq!(eval(code), format!("Uncaught error: {}", message));
+    assert_eq!(vm(code), format!("Compile error: {}", message));
+    let code = "let f = fn() { f.x = 1 }; f()";
+    let message = "f is defined outside this function and can't be assigned";
+    assert_eq!(eval(code), format!("Uncaught error: {}", message));
+    assert_eq!(vm(code), format!("Compile error: {}", message));
+    let code = "len.x = 1";
+    assert_eq!(
+        eval(code),
+        "Uncaught error: len is a builtin and can't be assigned"
+    );
+    assert_eq!(
+        vm(code),
+        "Compile error: len is a builtin and can't be assigned"
+    );
+    check(
+        "struct P { x } let p = P(1); let f = fn() { let q = P(2); q.x = p.x + 5; p.x = q.x }; f(); p.x",
+        "6",
+    );
+}
+
+#[test]
+fn structs_are_not_hash_keys() {
+    check(
+        "struct P { x } let f = fn() { 1 };
+        puts(try { {P: 1} } catch (e) { e });
+        puts(try { {f: 1} } catch (e) { e });
+        len({(1, \"a\"): 1, P(1).x: 2})",
+        "error: Invalid key for hash\nerror: Invalid key for hash\n2",
+    );
+}
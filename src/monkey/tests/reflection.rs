@@ -0,0 +1,78 @@
+use super::check;
+
+#[test]
+fn type_of_values() {
+    check(
+        r#"struct Point { x, y }
+        puts(type_of(1), type_of("s"), type_of(true), type_of([]), type_of({}), type_of((1, 2)));
+        puts(type_of(fn(x) { x }), type_of(len), type_of(Point(1, 2)), type_of(Point));
+        type_of(find([], len))"#,
+        "int\nstring\nbool\narray\nhash\ntuple\nfunction\nbuiltin\nPoint\nstruct\n'null'",
+    );
+}
+
+#[test]
+fn conversions() {
+    check(
+        r#"puts("n=" + str(5), str("s"), str([1, "a"]));
+        (int("42"), int(" -7 "), int(true), int(3))"#,
+        "n=5\ns\n[1, 'a']\n(42, -7, 1, 3)",
+    );
+    check(
+        r#"puts(try { int("4x") } catch (e) { e }); int([])"#,
+        "error: Can't parse '4x' as an int\nUncaught error: Expected string, bool or int for int builtin",
+    );
+}
+
+//Functions with a rest parameter take any number of args, which is -1 like
+//for puts
+#[test]
+fn arity_of_callables() {
+    check(
+        "struct Point { x, y } let n = 1; fn named(a, ...r) { a }
+        puts(arity(fn(a, b) { a }), arity(fn(a, b = 1) { a }), arity(fn() { n }));
+        puts(arity(fn(a, b = 1, ...r) { a }), arity(fn(...r) { n }), arity(named));
+        puts(arity(len), arity(map), arity(range), arity(puts), arity(Point));
+        arity(gen fn(x) { yield x; })",
+        "2\n2\n0\n-1\n-1\n-1\n1\n2\n-1\n-1\n2\n1",
+    );
+    check(
+        "arity(1)",
+        "Uncaught error: Expected function for arity builtin",
+    );
+}
+
+#[test]
+fn repr_and_print() {
+    check(
+        r#"let h = {"b": [1, {"c": 2}], "a": (1, "x")};
+        puts(repr("s"), repr([1, "a"]), repr(h), str(h));
+        print("a", 1, [2]);
+        print("\n");
+        puts([len, upper], {});
+        "interp ${"s"} ${[1, "s"]}""#,
+        "'s'\n[1, 'a']\n{ 'a': (1, 'x'), 'b': [1, { 'c': 2 }] }\n{ 'a': (1, 'x'), 'b': [1, { 'c': 2 }] }\na 1 [2]\n[builtin len, builtin upper]\n{}\n'interp s [1, 's']'",
+    );
+}
+
+#[test]
+fn inspect_nested_values() {
+    check(
+        r#"let h = {"b": [1, {"c": 2}], "a": (1, "x")};
+        puts(inspect([1, 2]), inspect(h));
+        inspect([[1], [], {}])"#,
+        "[1, 2]
+{
+  'a': (1, 'x'),
+  'b': [
+    1,
+    { 'c': 2 },
+  ],
+}
+'[
+  [1],
+  [],
+  {},
+]'",
+    );
+}
@@ -0,0 +1,113 @@
+use super::check;
+use crate::monkey::regex::Regex;
+
+#[test]
+fn matches_and_groups() {
+    check(
+        r#"puts(re_match("(\d+)-(\d+)", "call 555-1234 now"), re_match("^abc$", "abcd"));
+        puts(re_match("a(x)?b", "ab"), re_match("(a|ab)(c|bcd)(d*)", "abcd"));
+        puts(re_match("a.*?b", "aXbYb"), re_match("a.*b", "aXbYb"));
+        puts(re_match("(?:ab)+", "ababx"), re_match("((a)|b)+", "ab"));
+        re_match("é+", "ééé!")"#,
+        "['555-1234', '555', '1234']
+null
+['ab', null]
+['abcd', 'a', 'bcd', '']
+['aXb']
+['aXbYb']
+['abab']
+['ab', 'b', 'a']
+['ééé']",
+    );
+}
+
+#[test]
+fn find_all() {
+    check(
+        r#"puts(re_find_all("\d+", "a1 b22 c333"), re_find_all("(\w)(\d)", "a1 b2"), re_find_all("x*", "axxb"));
+        puts(re_find_all("colou?r", "color colour colouur"));
+        puts(re_find_all("a{2,3}", "a aa aaa aaaa"), re_find_all("a{2}", "aaaaa"), re_find_all("x{a", "x{a"));
+        puts(re_find_all("[^a-c\d]+", "abxyz12def"), re_find_all("[]a]", "]a"), re_find_all("[a-]", "-a"));
+        (re_find_all("\bcat\b", "cat concat cat."), re_find_all("\Bcat", "cat concat"))"#,
+        "['1', '22', '333']
+[['a1', 'a', '1'], ['b2', 'b', '2']]
+['', 'xx', '', '']
+['color', 'colour']
+['aa', 'aaa', 'aaa']
+['aa', 'aa']
+['x{a']
+['xyz', 'def']
+[']', 'a']
+['-', 'a']
+(['cat', 'cat'], ['cat'])",
+    );
+}
+
+#[test]
+fn replace_and_split() {
+    check(
+        r#"puts(re_replace("(\w+)@(\w+)", "bob@home and al@work", "$2:$1 $$"));
+        puts(re_replace("\d", "a1b2", fn(m) { str(int(m[0]) * 10) }), re_replace("", "abc", "-"));
+        puts(re_split("\s*,\s*", "a , b,c ,d"), re_split(",", ""));
+        re_split("", "abc")"#,
+        "home:bob $ and work:al $
+a10b20
+-a-b-c-
+['a', 'b', 'c', 'd']
+['']
+['', 'a', 'b', 'c', '']",
+    );
+}
+
+#[test]
+fn nested_repetition_stays_fast() {
+    check(
+        r#"re_match("(a*)*b", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaac")"#,
+        "null",
+    );
+}
+
+#[test]
+fn regex_errors() {
+    check(
+        r#"let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { re_match("(ab", "x") });
+        t(fn() { re_match("a)", "x") });
+        t(fn() { re_match("*a", "x") });
+        t(fn() { re_match("[a", "x") });
+        t(fn() { re_match("a{3,2}", "x") });
+        t(fn() { re_match("a{5000}", "x") });
+        t(fn() { re_match("(?=a)", "x") });
+        t(fn() { re_match("[z-a]", "x") });
+        re_replace("a", "a", "$3")"#,
+        "error: Invalid regex '(ab': missing ')'
+error: Invalid regex 'a)': unmatched ')'
+error: Invalid regex '*a': nothing to repeat before '*'
+error: Invalid regex '[a': missing ']'
+error: Invalid regex 'a{3,2}': repeat counts out of order
+error: Invalid regex 'a{5000}': repeat counts have to be at most 1000
+error: Invalid regex '(?=a)': unknown group kind
+error: Invalid regex '[z-a]': range z-a out of order
+Uncaught error: No group 3 in regex",
+    );
+}
+
+#[test]
+fn positions_count_chars() {
+    let regex = Regex::new("l(l)?").unwrap();
+    let text = "héllo".chars().collect::<Vec<_>>();
+    assert_eq!(regex.groups(), 1);
+    assert_eq!(
+        regex.find_at(&text, 0),
+        Some(vec![Some(2), Some(4), Some(3), Some(4)])
+    );
+    assert_eq!(regex.find_at(&text, 4), None);
+}
+
+#[test]
+fn pattern_size_is_limited() {
+    assert!(matches!(
+        Regex::new("(a{1000}){1000}"),
+        Err(e) if e == "pattern is too large"
+    ));
+}
@@ -0,0 +1,59 @@
+use super::{check, eval, parse, vm};
+use crate::monkey::vm::{Compilation, CompiledContext};
+
+#[test]
+fn block_lets_shadow_outer_names() {
+    check("let x = 1; if (true) { let x = 2; puts(x); }; x", "2\n1");
+    check(
+        "let f = fn(a) {
+            let y = 10;
+            if (a > 0) { let y = a; puts(y * 2); } else { let w = 5; puts(w); };
+            y + 7
+        };
+        puts(f(3));
+        f(0)",
+        "6\n17\n5\n17",
+    );
+    check(
+        r#"let e = "outer"; try { throw "boom"; } catch (e) { puts(e); }; e"#,
+        "boom\n'outer'",
+    );
+}
+
+#[test]
+fn closures_keep_block_variables() {
+    check(
+        "let mk = fn() { if (true) { let k = 42; fn() { k } } else { fn() { 0 } } }; mk()()",
+        "42",
+    );
+    check(
+        "let adder = fn(n) { fn(m) { n + m } }; let add2 = adder(2); let n = 100; add2(1)",
+        "3",
+    );
+}
+
+#[test]
+fn blocks_in_generators() {
+    check(
+        "let g = gen fn() { let v = 1; if (true) { let v = 2; yield v; }; yield v; };
+        let it = g();
+        [next(it), next(it)]",
+        "[2, 1]",
+    );
+}
+
+#[test]
+fn block_lets_do_not_leak() {
+    let code = "let leak = fn() { if (true) { let hidden = 1; }; hidden }; leak()";
+    assert_eq!(eval(code), "Uncaught error: Undefined variable hidden");
+    assert_eq!(vm(code), "Compile error: Undefined variable hidden");
+}
+
+#[test]
+fn later_blocks_reuse_local_slots() {
+    let mut ctx = CompiledContext::default();
+    parse("fn() { if (true) { let a = 1; a }; if (true) { let b = 2; let c = 3; b + c } }")
+        .compile(&mut ctx);
+    let f = format!("{:?}", ctx.get_constants().last().unwrap());
+    assert!(f.contains("num_locals: 2"), "{}", f);
+}
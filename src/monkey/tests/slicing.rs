@@ -0,0 +1,46 @@
+use super::check;
+
+#[test]
+fn negative_indexes() {
+    check("let a = [1, 2, 3]; puts(a[-1], a[-3]); a[0]", "3\n1\n1");
+    check(r#""hello"[-1]"#, "'o'");
+}
+
+#[test]
+fn array_slices() {
+    check(
+        "let a = [1, 2, 3, 4, 5]; puts(a[1:3], a[:2], a[3:], a[-2:], a[:]); a[4:1]",
+        "[2, 3]\n[1, 2]\n[4, 5]\n[4, 5]\n[1, 2, 3, 4, 5]\n[]",
+    );
+    //bounds past either end are clamped
+    check("[1, 2, 3][-5:10]", "[1, 2, 3]");
+}
+
+#[test]
+fn string_slices_count_chars() {
+    check(
+        r#"let s = "héllo"; puts(s[1], s[1:3], s[:-2], s[-10:]); len(s)"#,
+        "é\nél\nhél\nhéllo\n5",
+    );
+}
+
+#[test]
+fn slice_in_recursion() {
+    check(
+        "let sum = fn(xs) { if (len(xs) == 0) { 0 } else { xs[0] + sum(xs[1:]) } }; sum([1, 2, 3, 4])",
+        "10",
+    );
+}
+
+#[test]
+fn bad_indexes() {
+    check("[1, 2][5]", "Uncaught error: Index out of bounds for array");
+    check(
+        r#""abc"[3]"#,
+        "Uncaught error: Index out of bounds for string",
+    );
+    check(
+        r#"try { [1, 2][:"a"] } catch (e) { puts(e) }; 5[1:2]"#,
+        "error: Expected int bounds for slice\nUncaught error: Expected array or string for slice",
+    );
+}
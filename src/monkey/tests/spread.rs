@@ -0,0 +1,54 @@
+use super::check;
+
+#[test]
+fn spread_into_arrays() {
+    check(
+        r#"let a = [1, 2]; let b = [5, 6];
+        puts([...a, 3, 4, ...b], [...a], [0, ...a, ...b, 7, 8]);
+        [...(1, "x"), ...[]]"#,
+        "[1, 2, 3, 4, 5, 6]\n[1, 2]\n[0, 1, 2, 5, 6, 7, 8]\n[1, 'x']",
+    );
+}
+
+#[test]
+fn spread_into_hashes() {
+    //later entries win
+    check(
+        r#"let defaults = {"color": "red", "size": 1};
+        let h = {...defaults, "size": 3};
+        let h2 = {"size": 9, ...defaults};
+        puts(h["color"], h["size"], h2["size"]);
+        {...{"a": 1}, ...{"a": 2}}["a"]"#,
+        "red\n3\n1\n2",
+    );
+}
+
+#[test]
+fn spread_into_calls() {
+    check(
+        "let add3 = fn(x, y, z) { x + y + z }; let all = fn(...xs) { xs };
+        puts(add3(...[1, 2, 3]), add3(10, ...[20, 30]), all(...[1, 2], 9, ...(5, 6)));
+        len(...[\"abc\"])",
+        "6\n60\n[1, 2, 9, 5, 6]\n3",
+    );
+    //spread calls in tail position are still tail calls
+    check(
+        "let loop = fn(n, acc) { if (n == 0) { acc } else { loop(...[n - 1, acc + n]) } }; loop(10000, 0)",
+        "50005000",
+    );
+}
+
+#[test]
+fn spread_errors() {
+    check(
+        "let add3 = fn(x, y, z) { x + y + z };
+        puts(try { [...5] } catch (e) { e });
+        puts(try { {...[1]} } catch (e) { e });
+        puts(try { add3(...5) } catch (e) { e });
+        add3(...[1])",
+        "error: Expected array or tuple to spread
+error: Expected hash to spread
+error: Expected array or tuple to spread
+Uncaught error: Expected 3 args for call but got 1",
+    );
+}
@@ -0,0 +1,63 @@
+use super::check;
+
+#[test]
+fn split_and_join() {
+    check(
+        r#"puts(split("a,b,,c", ","), split("héllo", ""), chars("ab")); join(["x", "y", "z"], "-")"#,
+        "['a', 'b', '', 'c']\n['h', 'é', 'l', 'l', 'o']\n['a', 'b']\n'x-y-z'",
+    );
+}
+
+#[test]
+fn transform() {
+    check(
+        r#"puts(trim("  hi \n"), upper("abc"), lower("ÀBC")); replace("a-b-c", "-", "+")"#,
+        "hi\nABC\nàbc\n'a+b+c'",
+    );
+}
+
+#[test]
+fn search() {
+    //positions count chars, like indexing
+    check(
+        r#"puts(contains("haystack", "st"), contains("abc", "z"));
+        puts(starts_with("monkey", "mon"), ends_with("monkey", "key"));
+        (index_of("héllo", "llo"), index_of("abc", "z"))"#,
+        "true\nfalse\ntrue\ntrue\n(2, -1)",
+    );
+}
+
+#[test]
+fn code_points() {
+    check(r#"puts(ord("A"), chr(955)); ord(chr(128));"#, "65\nλ\n128");
+}
+
+#[test]
+fn len_counts_chars() {
+    check(r#"(len("héllo"), len(""), len("λλ"))"#, "(5, 0, 2)");
+}
+
+#[test]
+fn natives_are_values() {
+    check(
+        r#"let f = upper; puts(f("via var"), upper); type_of(upper)"#,
+        "VIA VAR\nbuiltin upper\n'builtin'",
+    );
+}
+
+#[test]
+fn string_errors() {
+    check(
+        r#"let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { ord("ab") });
+        t(fn() { join([1], ",") });
+        t(fn() { chr(-1) });
+        t(fn() { trim() });
+        upper(1)"#,
+        "error: Expected single char string for ord builtin
+error: Expected array of strings and separator for join builtin
+error: Expected valid code point for chr builtin
+error: Expected single string for trim builtin
+Uncaught error: Expected single string for upper builtin",
+    );
+}
@@ -0,0 +1,79 @@
+use super::{check, eval, vm};
+
+#[test]
+fn deep_tail_recursion() {
+    check(
+        "let sum = fn(n, acc) { if (n == 0) { acc } else { let m = n - 1; sum(m, acc + n) } };
+        sum(100000, 0)",
+        "5000050000",
+    );
+    //returns and the last statement are both tail positions
+    check(
+        "let fizzbuzz = fn(n, acc) {
+            if (n == 0) { return acc; }
+            if (n % 15 == 0) { return fizzbuzz(n - 1, acc + 15); }
+            if (n % 5 == 0) { return fizzbuzz(n - 1, acc + 5); }
+            if (n % 3 == 0) { return fizzbuzz(n - 1, acc + 3); }
+            fizzbuzz(n - 1, acc + 1);
+        };
+        fizzbuzz(30000, 0)",
+        "90000",
+    );
+}
+
+#[test]
+fn mutual_tail_recursion() {
+    check(
+        "let is_even = fn(n) { if (n == 0) { true } else { is_odd(n - 1) } };
+        let is_odd = fn(n) { if (n == 0) { false } else { is_even(n - 1) } };
+        puts(is_odd(7));
+        is_even(50001)",
+        "true\nfalse",
+    );
+}
+
+#[test]
+fn calls_that_are_not_tail_calls() {
+    check(
+        "let fib = fn(n) { if (n < 2) { return 1; } else { return fib(n - 1) + fib(n - 2); } }; fib(15)",
+        "987",
+    );
+    //a call inside try has to come back to its handler
+    check(
+        r#"let guarded = fn(n) { try { if (n == 0) { throw "bottom"; } guarded(n - 1) } catch (e) { e } };
+        guarded(10)"#,
+        "'bottom'",
+    );
+}
+
+#[test]
+fn tail_calls_to_other_callables() {
+    check(
+        "let via_builtin = fn(a) { len(a) }; let mk = fn(x) { struct P { x } P(x) };
+        puts(via_builtin([1, 2, 3]));
+        mk(3)",
+        "3\nP { x: 3 }",
+    );
+    check(
+        "let twice = fn(x) { x * 2 }; fn() { twice(1, 2) }()",
+        "Uncaught error: Expected 1 args for call but got 2",
+    );
+}
+
+#[test]
+fn deep_recursion_overflows() {
+    let code = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };
+        puts(f(100));
+        puts(try { f(100000) } catch (e) { e });
+        f(100000)";
+    let expected = "100\nerror: Stack overflow\nUncaught error: Stack overflow";
+    //debug builds use far more host stack per call than the limit allows for
+    let evaluated = std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(move || eval(code))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(evaluated, expected);
+    assert_eq!(vm(code), expected);
+}
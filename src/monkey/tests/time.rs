@@ -0,0 +1,51 @@
+use super::{check, NOW};
+
+#[test]
+fn now_reads_the_injected_clock() {
+    check("now()", &NOW.to_string());
+    check(
+        r#"format_time(now(), "%Y-%m-%d %H:%M:%S%.3f")"#,
+        "'2023-11-14 22:13:20.123'",
+    );
+}
+
+#[test]
+fn durations() {
+    check(
+        r#"let t = now();
+        let later = t + duration(90, "minutes");
+        puts(format_time(later, "%H:%M"), duration_in(later - t, "hours"));
+        duration_in(later - t, "minutes")"#,
+        "23:43\n1\n90",
+    );
+    check(
+        r#"duration_in(parse_time("2024-03-01", "%Y-%m-%d") - parse_time("2024-01-01", "%Y-%m-%d"), "days")"#,
+        "60",
+    );
+}
+
+#[test]
+fn parse_and_format() {
+    check(
+        r#"puts(parse_time("2024-02-29T12:00:00Z"), parse_time("2024-02-29T12:00:00+02:00"));
+        puts(parse_time("29/02/2024", "%d/%m/%Y"), parse_time("2024-02-29 08:30", "%Y-%m-%d %H:%M"));
+        puts(format_time(-1, "%Y"));
+        format_time(parse_time("1999-12-31", "%Y-%m-%d") + duration(1, "days"), "%A %d %B %Y")"#,
+        "1709208000000\n1709200800000\n1709164800000\n1709195400000\n1969\n'Saturday 01 January 2000'",
+    );
+}
+
+#[test]
+fn time_errors() {
+    check(
+        r#"let t = fn(f) { try { f() } catch (e) { puts(e) } };
+        t(fn() { parse_time("yesterday") });
+        t(fn() { format_time(0, "%Q") });
+        t(fn() { duration(1, "fortnights") });
+        now(1)"#,
+        "error: Can't parse 'yesterday' as a time
+error: Expected valid format string for format_time builtin
+error: Expected milliseconds, seconds, minutes, hours, days or weeks for duration builtin
+Uncaught error: Expected no arguments for now builtin",
+    );
+}
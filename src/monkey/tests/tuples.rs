@@ -0,0 +1,47 @@
+use super::check;
+
+#[test]
+fn tuple_literals() {
+    check(
+        r#"let t = (1, "a", true); puts(t, t[1], t[-1], (1,), (), (1 + 2) * 3); type_of((1, [2]))"#,
+        "(1, 'a', true)\na\ntrue\n(1,)\n()\n9\n'tuple'",
+    );
+    check("(1, 2)[5]", "Uncaught error: Index out of bounds for tuple");
+}
+
+#[test]
+fn multiple_return_values() {
+    check(
+        "let divmod = fn(a, b) { (a / b, a % b) }; let (q, r) = divmod(17, 5); puts(q); r",
+        "3\n2",
+    );
+    check(
+        r#"let early = fn() { return ("x", 9); }; let (s, v) = early(); puts(s); v"#,
+        "x\n9",
+    );
+    check("let (a, b) = (1, 2); let (b, a) = (a, b); (a, b)", "(2, 1)");
+}
+
+#[test]
+fn tuples_as_hash_keys() {
+    check(
+        r#"let h = {(1, 2): "one-two", (2, 1): "two-one"}; puts(h[(1, 2)]); h[(2, 1)]"#,
+        "one-two\n'two-one'",
+    );
+}
+
+#[test]
+fn tuples_holding_arrays() {
+    check(
+        "let nested = ([1, 2], 3); puts(nested); let (arr, n) = nested; arr[0] + n",
+        "([1, 2], 3)\n4",
+    );
+}
+
+#[test]
+fn destructuring_errors() {
+    check(
+        "puts(try { let (a, b) = (1, 2, 3); a } catch (e) { e }); let (a, b) = 5;",
+        "error: Wrong number of names to destructure tuple\nUncaught error: Expected tuple to destructure",
+    );
+}
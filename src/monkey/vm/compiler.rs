@@ -1,9 +1,9 @@
-use super::types::{Builtin, Closure, CompiledFn, Instruction, Op, Primitive};
+use super::types::{Builtin, Closure, CompiledFn, Instruction, Op, Primitive, StructDef};
 use crate::monkey::{
     lexer::Token,
-    parser::{Block, Expr, Program},
+    parser::{Block, Expr, HashEntry, If, Program},
 };
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, rc::Rc, str::FromStr};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum SymbolScope {
@@ -19,13 +19,28 @@ struct Symbol {
     name: String,
     index: u16,
     scope: SymbolScope,
+    constant: bool,
+    //constant pool index of a const whose value is known when compiling,
+    //reads of it push that instead
+    inline: Option<u16>,
+}
+
+//Names defined inside an if or try block go away when it ends
+#[derive(Debug, Clone)]
+struct BlockScope {
+    //what each name defined in the block hid when it was first defined
+    shadowed: Vec<(String, Option<Symbol>)>,
+    next_index: usize,
 }
 
 #[derive(Debug, Default, Clone)]
 struct SymbolTable {
     outer: Option<Box<SymbolTable>>,
     symbols: HashMap<String, Symbol>,
+    //slots the frame needs, local slots are reused after a block ends
     num_definitions: usize,
+    next_index: usize,
+    blocks: Vec<BlockScope>,
     free: Vec<Symbol>,
 }
 
@@ -35,12 +50,46 @@ impl SymbolTable {
             outer: Some(Box::new(outer)),
             symbols: HashMap::new(),
             num_definitions: 0,
+            next_index: 0,
+            blocks: Vec::new(),
             free: Vec::new(),
         }
     }
+    pub fn enter_block(&mut self) {
+        self.blocks.push(BlockScope {
+            shadowed: Vec::new(),
+            next_index: self.next_index,
+        });
+    }
+    pub fn exit_block(&mut self) {
+        let block = self.blocks.pop().expect("No block to exit");
+        for (name, symbol) in block.shadowed.into_iter().rev() {
+            match symbol {
+                Some(symbol) => self.symbols.insert(name, symbol),
+                None => self.symbols.remove(&name),
+            };
+        }
+        //globals outlive the block in closures, so only locals are reused
+        if self.outer.is_some() {
+            self.next_index = block.next_index;
+        }
+    }
     pub fn define(&mut self, name: String) -> Symbol {
-        let index = self.num_definitions as u16;
-        self.num_definitions += 1;
+        if let Some(block) = self.blocks.last_mut() {
+            if !block.shadowed.iter().any(|(n, _)| *n == name) {
+                let hidden = self.symbols.get(&name).cloned();
+                block.shadowed.push((name.clone(), hidden));
+            }
+        } else if self.outer.is_none() {
+            //globals keep their slot when they are defined again, so
+            //hoisted names and the let that sets them agree
+            if let Some(symbol) = self.symbols.get(&name) {
+                return symbol.clone();
+            }
+        }
+        let index = self.next_index as u16;
+        self.next_index += 1;
+        self.num_definitions = self.num_definitions.max(self.next_index);
         let scope = if self.outer.is_some() {
             SymbolScope::Local
         } else {
@@ -50,40 +99,75 @@ impl SymbolTable {
             name: name.clone(),
             index,
             scope,
+            constant: false,
+            inline: None,
         };
         self.symbols.insert(name, symbol.clone());
         symbol
     }
+    //Fails when `name` is a constant defined in the current block or function
+    pub fn check_define(&self, name: &str) -> Result<(), String> {
+        let here = match self.blocks.last() {
+            Some(block) => block.shadowed.iter().any(|(n, _)| n == name),
+            None => true,
+        };
+        match self.symbols.get(name) {
+            Some(symbol)
+                if here
+                    && symbol.constant
+                    && matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) =>
+            {
+                Err(format!("{} is a constant and can't be defined again", name))
+            }
+            _ => Ok(()),
+        }
+    }
+    pub fn define_const(&mut self, name: String, inline: Option<u16>) -> Symbol {
+        let mut symbol = self.define(name.clone());
+        symbol.constant = true;
+        symbol.inline = inline;
+        self.symbols.insert(name, symbol.clone());
+        symbol
+    }
+    //The constant pool index of `name` if it's an inlined const
+    fn inline(&self, name: &str) -> Option<u16> {
+        match self.symbols.get(name) {
+            Some(symbol) => symbol.inline,
+            None => self.outer.as_ref().and_then(|outer| outer.inline(name)),
+        }
+    }
     pub fn define_free(&mut self, original: Symbol) -> Symbol {
         let symbol = Symbol {
             name: original.name.clone(),
             index: self.free.len() as u16,
             scope: SymbolScope::Free,
+            constant: original.constant,
+            inline: None,
         };
         self.free.push(original);
         self.symbols.insert(symbol.name.clone(), symbol.clone());
         symbol
     }
+    //The name of the function being compiled, it has no slot since
+    //`CurrentClosure` pushes the running closure
     pub fn define_fn(&mut self, name: String) {
         let symbol = Symbol {
             name: name.clone(),
-            index: 23,
+            index: 0,
             scope: SymbolScope::Function,
+            constant: false,
+            inline: None,
         };
         self.symbols.insert(name, symbol);
     }
     pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
-        if let Ok(builtin) = Builtin::from_str(name) {
-            Some(Symbol {
-                name: name.to_string(),
-                index: builtin as u16,
-                scope: SymbolScope::Builtin,
-            })
-        } else if let Some(val) = self.symbols.get(name) {
+        if let Some(val) = self.symbols.get(name) {
             Some(val.clone())
         } else if let Some(outer) = &mut self.outer {
             if let Some(val) = outer.resolve(name) {
-                if matches!(val.scope, SymbolScope::Global | SymbolScope::Builtin) {
+                if matches!(val.scope, SymbolScope::Global | SymbolScope::Builtin)
+                    || val.inline.is_some()
+                {
                     Some(val)
                 } else {
                     Some(self.define_free(val))
@@ -91,6 +175,16 @@ impl SymbolTable {
             } else {
                 None
             }
+        } else if let Ok(builtin) = Builtin::from_str(name) {
+            //builtins come after every scope, like env lookups in the
+            //evaluator, so user definitions shadow them
+            Some(Symbol {
+                name: name.to_string(),
+                index: builtin.index() as u16,
+                scope: SymbolScope::Builtin,
+                constant: false,
+                inline: None,
+            })
         } else {
             None
         }
@@ -101,25 +195,31 @@ impl SymbolTable {
 struct Scope {
     instructions: Vec<Instruction>,
     byte_size: usize,
+    //calls whose value is returned right away can reuse the frame, not in
+    //generators or the main program
+    tail_calls: bool,
+    //try blocks around the code being compiled, their handlers live in the
+    //frame so calls inside them can't replace it
+    try_depth: usize,
 }
 
 impl Scope {
     fn set_return(&mut self) {
-        if let Some(last) = self.instructions.last_mut() {
-            if last.op == Op::Pop {
-                last.op = Op::ReturnVal;
-            }
-        } else {
-            self.instructions.push(Instruction::new(Op::Return));
+        match self.instructions.last_mut() {
+            Some(last) if last.op == Op::Pop => last.op = Op::ReturnVal,
+            Some(last) if last.op == Op::ReturnVal => {}
+            _ => self.instructions.push(Instruction::new(Op::Return)),
         }
     }
-    fn remove_last_pop(&mut self) {
+    fn remove_last_pop(&mut self) -> bool {
         if let Some(last) = self.instructions.last() {
             if last.op == Op::Pop {
                 self.byte_size -= last.len();
                 self.instructions.pop();
+                return true;
             }
         }
+        false
     }
 }
 
@@ -127,6 +227,13 @@ pub struct CompiledContext {
     symbols: SymbolTable,
     scopes: Vec<Scope>,
     constants: Vec<Primitive>,
+    //slot of each field name in the struct that declared it first, used as
+    //a hint by the field opcodes
+    field_slots: HashMap<String, u8>,
+    //constant holding each field name, every access to a field shares it
+    field_names: HashMap<String, u16>,
+    //problems found while compiling, the program shouldn't be run if any
+    errors: Vec<String>,
 }
 
 impl Default for CompiledContext {
@@ -135,6 +242,9 @@ impl Default for CompiledContext {
             symbols: SymbolTable::default(),
             constants: Vec::new(),
             scopes: vec![Scope::default()],
+            field_slots: HashMap::new(),
+            field_names: HashMap::new(),
+            errors: Vec::new(),
         }
     }
 }
@@ -148,10 +258,16 @@ impl CompiledContext {
                 body,
                 num_locals,
                 num_args: 0,
+                num_required: 0,
+                variadic: false,
+                generator: false,
             },
             Vec::new(),
         )
     }
+    pub fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.errors)
+    }
     pub fn get_constants(&self) -> Vec<Primitive> {
         self.constants.clone()
     }
@@ -214,6 +330,10 @@ impl CompiledContext {
         }));
     }
     fn emit_symbol(&mut self, symbol: Symbol) {
+        if let Some(index) = symbol.inline {
+            self.emit(Instruction::new_u16(Op::Constant, index));
+            return;
+        }
         match symbol.scope {
             SymbolScope::Global => self.emit(Instruction::new_u16(Op::GetGlobal, symbol.index)),
             SymbolScope::Local => self.emit(Instruction::new_u16(Op::GetLocal, symbol.index)),
@@ -224,6 +344,267 @@ impl CompiledContext {
             SymbolScope::Function => self.emit(Instruction::new(Op::CurrentClosure)),
         }
     }
+    fn emit_field(&mut self, op: Op, name: &str) {
+        let slot = self.field_slots.get(name).copied().unwrap_or(0);
+        let constant = match self.field_names.get(name) {
+            Some(&constant) => constant,
+            None => {
+                self.constants.push(Primitive::String_(name.to_string()));
+                let constant = self.constants.len() as u16 - 1;
+                self.field_names.insert(name.to_string(), constant);
+                constant
+            }
+        };
+        self.emit(Instruction::new_u16_u8(op, constant, slot));
+    }
+    //Stores the value on top of the stack into an assignment target, updated
+    //records are stored back into their parent until a variable is reached
+    fn assign(&mut self, target: &Expr) {
+        match target {
+            Expr::Identifier(name) => {
+                let Some(symbol) = self.symbols.resolve(name) else {
+                    self.errors.push(format!("Undefined variable {}", name));
+                    return;
+                };
+                if symbol.constant {
+                    self.errors
+                        .push(format!("{} is a constant and can't be assigned", name));
+                    return;
+                }
+                let op = match symbol.scope {
+                    SymbolScope::Global => Op::SetGlobal,
+                    SymbolScope::Local => Op::SetLocal,
+                    //closures hold copies of what they capture, so a store
+                    //wouldn't reach the variable
+                    SymbolScope::Free | SymbolScope::Function => {
+                        self.errors.push(format!(
+                            "{} is defined outside this function and can't be assigned",
+                            name
+                        ));
+                        return;
+                    }
+                    SymbolScope::Builtin => {
+                        self.errors
+                            .push(format!("{} is a builtin and can't be assigned", name));
+                        return;
+                    }
+                };
+                self.emit(Instruction::new_u16(op, symbol.index));
+            }
+            Expr::Field(field) => {
+                field.left.compile(self);
+                self.emit_field(Op::SetField, &field.name);
+                self.assign(&field.left);
+            }
+            _ => self.errors.push("Invalid assignment target".to_string()),
+        }
+    }
+    //Compiles a block whose value stays on the stack, blocks that don't end
+    //in an expression leave null
+    fn statement(&mut self, s: &Expr) {
+        s.compile(self);
+        if leaves_value(s) {
+            self.emit(Instruction::new(Op::Pop));
+        }
+    }
+    //Defines `name` and stores the value on top of the stack in it
+    fn define_value(&mut self, name: &str) {
+        if let Err(e) = self.symbols.check_define(name) {
+            self.errors.push(e);
+        }
+        let symbol = self.symbols.define(name.to_string());
+        self.set_symbol(&symbol);
+    }
+    fn define_const(&mut self, name: &str, inline: Option<u16>) {
+        if let Err(e) = self.symbols.check_define(name) {
+            self.errors.push(e);
+        }
+        let symbol = self.symbols.define_const(name.to_string(), inline);
+        self.set_symbol(&symbol);
+    }
+    fn set_symbol(&mut self, symbol: &Symbol) {
+        let op = if symbol.scope == SymbolScope::Global {
+            Op::SetGlobal
+        } else {
+            Op::SetLocal
+        };
+        self.emit(Instruction::new_u16(op, symbol.index));
+    }
+    //The value of an expression made only of literals and inlined consts,
+    //`None` when it has to be computed at run time or would fail there
+    fn fold(&self, expr: &Expr) -> Option<Primitive> {
+        match expr {
+            Expr::Int(i) => Some(Primitive::Int(*i)),
+            Expr::Bool(b) => Some(Primitive::Bool(*b)),
+            Expr::String(s) => Some(Primitive::String_(s.clone())),
+            Expr::Identifier(name) => self
+                .symbols
+                .inline(name)
+                .map(|index| self.constants[index as usize].clone()),
+            Expr::Prefix(p) => match (&p.token, self.fold(&p.right)?) {
+                (Token::Minus, Primitive::Int(i)) => i.checked_neg().map(Primitive::Int),
+                (Token::Bang, Primitive::Bool(b)) => Some(Primitive::Bool(!b)),
+                _ => None,
+            },
+            Expr::Infix(i) => match (self.fold(&i.left)?, self.fold(&i.right)?) {
+                (Primitive::Int(l), Primitive::Int(r)) => match i.token {
+                    Token::Plus => l.checked_add(r).map(Primitive::Int),
+                    Token::Minus => l.checked_sub(r).map(Primitive::Int),
+                    Token::Star => l.checked_mul(r).map(Primitive::Int),
+                    Token::Slash => l.checked_div(r).map(Primitive::Int),
+                    Token::Percent => l.checked_rem(r).map(Primitive::Int),
+                    Token::Eq => Some(Primitive::Bool(l == r)),
+                    Token::Neq => Some(Primitive::Bool(l != r)),
+                    Token::Lt => Some(Primitive::Bool(l < r)),
+                    Token::Gt => Some(Primitive::Bool(l > r)),
+                    _ => None,
+                },
+                (Primitive::Bool(l), Primitive::Bool(r)) => match i.token {
+                    Token::Eq => Some(Primitive::Bool(l == r)),
+                    Token::Neq => Some(Primitive::Bool(l != r)),
+                    _ => None,
+                },
+                (Primitive::String_(l), Primitive::String_(r)) => match i.token {
+                    Token::Plus => Some(Primitive::String_(l + &r)),
+                    Token::Eq => Some(Primitive::Bool(l == r)),
+                    Token::Neq => Some(Primitive::Bool(l != r)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    //Compiles a block whose value stays on the stack, `tail` is set when
+    //that value is returned right away
+    fn block_value(&mut self, block: &Block, tail: bool) {
+        self.symbols.enter_block();
+        self.block_statements(block, tail);
+        self.symbols.exit_block();
+    }
+    fn block_statements(&mut self, block: &Block, tail: bool) {
+        match block.0.split_last() {
+            Some((last, stmts)) if tail && leaves_value(last) => {
+                for s in stmts {
+                    self.statement(s);
+                }
+                self.compile_tail(last);
+            }
+            _ => {
+                block.compile(self);
+                if !self.scopes.last_mut().unwrap().remove_last_pop() {
+                    self.emit(Instruction::new(Op::Null));
+                }
+            }
+        }
+    }
+    //Compiles an expression whose value is returned, calls reuse the frame
+    fn compile_tail(&mut self, expr: &Expr) {
+        let scope = self.scopes.last().unwrap();
+        if !scope.tail_calls || scope.try_depth > 0 {
+            expr.compile(self);
+            return;
+        }
+        match expr {
+            Expr::Call(c) if !has_spread(&c.args) => {
+                c.expr.compile(self);
+                for arg in &c.args {
+                    arg.compile(self);
+                }
+                self.emit(Instruction::new_u8(Op::TailCall, c.args.len() as u8));
+            }
+            Expr::If(i) => self.if_(i, true),
+            _ => expr.compile(self),
+        }
+    }
+    //Builds an array with spread elements, runs of plain elements around
+    //them are collected with Array and joined on with Extend
+    fn spread_array(&mut self, elems: &[Expr]) {
+        let first = elems
+            .iter()
+            .position(|e| matches!(e, Expr::Spread(_)))
+            .unwrap_or(elems.len());
+        for elem in &elems[..first] {
+            elem.compile(self);
+        }
+        self.emit(Instruction::new_u16(Op::Array, first as u16));
+        let mut run = 0;
+        for elem in &elems[first..] {
+            if let Expr::Spread(spread) = elem {
+                if run > 0 {
+                    self.emit(Instruction::new_u16(Op::Array, run));
+                    self.emit(Instruction::new(Op::Extend));
+                    run = 0;
+                }
+                spread.compile(self);
+                self.emit(Instruction::new(Op::Extend));
+            } else {
+                elem.compile(self);
+                run += 1;
+            }
+        }
+        if run > 0 {
+            self.emit(Instruction::new_u16(Op::Array, run));
+            self.emit(Instruction::new(Op::Extend));
+        }
+    }
+    //Builds a hash literal, runs of pairs around spread hashes are collected
+    //with Hash and merged in order with HashMerge
+    fn spread_hash(&mut self, entries: &[HashEntry]) {
+        let mut run = 0;
+        let mut started = false;
+        for entry in entries {
+            match entry {
+                HashEntry::Pair(key, value) => {
+                    key.compile(self);
+                    value.compile(self);
+                    run += 1;
+                }
+                HashEntry::Spread(spread) => {
+                    if run > 0 || !started {
+                        self.emit(Instruction::new_u16(Op::Hash, run));
+                        if started {
+                            self.emit(Instruction::new(Op::HashMerge));
+                        }
+                        run = 0;
+                        started = true;
+                    }
+                    spread.compile(self);
+                    self.emit(Instruction::new(Op::HashMerge));
+                }
+            }
+        }
+        if run > 0 || !started {
+            self.emit(Instruction::new_u16(Op::Hash, run));
+            if started {
+                self.emit(Instruction::new(Op::HashMerge));
+            }
+        }
+    }
+    fn if_(&mut self, i: &If, tail: bool) {
+        //Condition
+        i.condition.compile(self);
+        //Consequence
+        self.emit(Instruction::new_u16(Op::JmpIfFalse, 2323));
+        let jmp_false_pos = self.instructions_len() - 1;
+        self.block_value(&i.consequence, tail);
+        //Alternative
+        self.emit(Instruction::new_u16(Op::Jmp, 2323));
+        let jump_pos: usize = self.instructions_len() - 1;
+        self.set_nth_instruction(
+            jmp_false_pos,
+            Instruction::new_u16(Op::JmpIfFalse, self.instructions_size() as u16),
+        );
+        if let Some(alternative) = &i.alternative {
+            self.block_value(alternative, tail);
+        } else {
+            self.emit(Instruction::new(Op::Null));
+        }
+        self.set_nth_instruction(
+            jump_pos,
+            Instruction::new_u16(Op::Jmp, self.instructions_size() as u16),
+        );
+    }
     fn enter_scope(&mut self) {
         self.scopes.push(Scope::default());
         self.symbols = SymbolTable::new(self.symbols.clone());
@@ -248,25 +629,58 @@ pub trait Compilation {
     fn compile(&self, output: &mut CompiledContext);
 }
 
+fn has_spread(elems: &[Expr]) -> bool {
+    elems.iter().any(|e| matches!(e, Expr::Spread(_)))
+}
+
+//Statements that don't leave a value on the stack
+fn leaves_value(s: &Expr) -> bool {
+    !matches!(
+        s,
+        Expr::Return(_)
+            | Expr::Yield(_)
+            | Expr::Delegate(_)
+            | Expr::Let(_)
+            | Expr::Struct(_)
+            | Expr::Destructure(_)
+    )
+}
+
 impl Compilation for Block {
     fn compile(&self, out: &mut CompiledContext) {
         for s in &self.0 {
-            s.compile(out);
-            if !matches!(s, Expr::Return(_) | Expr::Let(_)) {
-                out.emit(Instruction::new(Op::Pop));
-            }
+            out.statement(s);
         }
     }
 }
 
 impl Compilation for Program {
     fn compile(&self, out: &mut CompiledContext) {
+        //Top level names are defined up front so functions can refer to the
+        //ones declared after them
         for s in &self.statements {
-            s.compile(out);
-            if !matches!(s, Expr::Return(_) | Expr::Let(_)) {
-                out.emit(Instruction::new(Op::Pop));
+            let names = match s {
+                Expr::Let(l) => vec![&l.name],
+                Expr::Destructure(d) => d.names.iter().collect(),
+                Expr::Struct(s) => vec![&s.name],
+                _ => continue,
+            };
+            for name in names {
+                if out.symbols.symbols.contains_key(name) {
+                    continue;
+                }
+                let symbol = out.symbols.define(name.clone());
+                out.constants.push(Primitive::String_(name.clone()));
+                out.emit(Instruction::new_u16_u16(
+                    Op::DeclareGlobal,
+                    symbol.index,
+                    out.constants.len() as u16 - 1,
+                ));
             }
         }
+        for s in &self.statements {
+            out.statement(s);
+        }
     }
 }
 
@@ -294,16 +708,43 @@ impl Compilation for Expr {
                     out.constants.len() as u16 - 1,
                 ));
             }
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    part.compile(out);
+                }
+                out.emit(Instruction::new_u16(Op::Concat, parts.len() as u16));
+            }
             Expr::Fn(f) => {
                 out.enter_scope();
+                out.scopes.last_mut().unwrap().tail_calls = !f.generator;
                 if let Some(name) = &f.name {
                     out.symbols.define_fn(name.clone());
                 }
                 for arg in &f.args {
-                    let symbol = out.symbols.define(arg.clone());
-                    out.emit_symbol(symbol);
+                    out.symbols.define(arg.name.clone());
+                }
+                if let Some(rest) = &f.rest {
+                    out.symbols.define(rest.clone());
                 }
-                f.body.compile(out);
+                //Missing optional args get their default before the body runs
+                for (ix, arg) in f.args.iter().enumerate() {
+                    if let Some(default) = &arg.default {
+                        out.emit(Instruction::new_u8_u16(Op::JmpIfArg, ix as u8, 2323));
+                        let jmp_pos = out.instructions_len() - 1;
+                        default.compile(out);
+                        out.emit(Instruction::new_u16(Op::SetLocal, ix as u16));
+                        out.set_nth_instruction(
+                            jmp_pos,
+                            Instruction::new_u8_u16(
+                                Op::JmpIfArg,
+                                ix as u8,
+                                out.instructions_size() as u16,
+                            ),
+                        );
+                    }
+                }
+                out.block_statements(&f.body, true);
+                out.emit(Instruction::new(Op::ReturnVal));
                 let num_locals = out.symbols.num_definitions as u16;
                 let free = out.symbols.free.clone();
                 let num_free = free.len() as u8;
@@ -312,6 +753,9 @@ impl Compilation for Expr {
                     body,
                     num_locals,
                     num_args: f.args.len() as u8,
+                    num_required: f.num_required() as u8,
+                    variadic: f.rest.is_some(),
+                    generator: f.generator,
                 }));
                 for symbol in free {
                     out.emit_symbol(symbol);
@@ -343,6 +787,17 @@ impl Compilation for Expr {
                 }
                 _ => panic!("Invalid infix token"),
             },
+            Expr::Slice(s) => {
+                s.left.compile(out);
+                //missing bounds are passed as null
+                for bound in [&s.start, &s.end] {
+                    match bound {
+                        Some(bound) => bound.compile(out),
+                        None => out.emit(Instruction::new(Op::Null)),
+                    }
+                }
+                out.emit(Instruction::new(Op::Slice));
+            }
             Expr::Prefix(p) => {
                 p.right.compile(out);
                 match p.token {
@@ -355,64 +810,137 @@ impl Compilation for Expr {
                     _ => unreachable!(),
                 }
             }
-            Expr::If(i) => {
-                //Condition
-                i.condition.compile(out);
-                //Consequence
-                out.emit(Instruction::new_u16(Op::JmpIfFalse, 2323));
-                let jmp_false_pos = out.instructions_len() - 1;
-                i.consequence.compile(out);
-                out.scopes.last_mut().unwrap().remove_last_pop();
-                //Alternative
+            Expr::If(i) => out.if_(i, false),
+            Expr::Try(t) => {
+                out.emit(Instruction::new_u16(Op::Try, 2323));
+                let try_pos = out.instructions_len() - 1;
+                out.scopes.last_mut().unwrap().try_depth += 1;
+                out.block_value(&t.body, false);
+                out.scopes.last_mut().unwrap().try_depth -= 1;
+                out.emit(Instruction::new(Op::EndTry));
                 out.emit(Instruction::new_u16(Op::Jmp, 2323));
-                let jump_pos: usize = out.instructions_len() - 1;
+                let jump_pos = out.instructions_len() - 1;
+                //The handler starts with the thrown value on the stack
                 out.set_nth_instruction(
-                    jmp_false_pos,
-                    Instruction::new_u16(Op::JmpIfFalse, out.instructions_size() as u16),
+                    try_pos,
+                    Instruction::new_u16(Op::Try, out.instructions_size() as u16),
                 );
-                if let Some(alternative) = &i.alternative {
-                    alternative.compile(out);
-                    out.scopes.last_mut().unwrap().remove_last_pop();
-                } else {
-                    out.emit(Instruction::new(Op::Null));
-                }
+                out.symbols.enter_block();
+                out.define_value(&t.name);
+                out.block_value(&t.handler, false);
+                out.symbols.exit_block();
                 out.set_nth_instruction(
                     jump_pos,
                     Instruction::new_u16(Op::Jmp, out.instructions_size() as u16),
                 );
             }
+            Expr::Throw(e) => {
+                e.compile(out);
+                out.emit(Instruction::new(Op::Throw));
+            }
+            Expr::Yield(e) => {
+                e.compile(out);
+                out.emit(Instruction::new(Op::Yield));
+            }
+            //gen; loop: RESUME; JMP_IF_DONE end; YIELD; JMP loop; end:
+            Expr::Delegate(e) => {
+                e.compile(out);
+                let loop_start = out.instructions_size() as u16;
+                out.emit(Instruction::new(Op::Resume));
+                out.emit(Instruction::new_u16(Op::JmpIfDone, 2323));
+                let done_pos = out.instructions_len() - 1;
+                out.emit(Instruction::new(Op::Yield));
+                out.emit(Instruction::new_u16(Op::Jmp, loop_start));
+                out.set_nth_instruction(
+                    done_pos,
+                    Instruction::new_u16(Op::JmpIfDone, out.instructions_size() as u16),
+                );
+            }
+            Expr::Struct(s) => {
+                for (ix, field) in s.fields.iter().enumerate() {
+                    out.field_slots.entry(field.clone()).or_insert(ix as u8);
+                }
+                out.constants.push(Primitive::Struct(Rc::new(StructDef {
+                    name: s.name.clone(),
+                    fields: s.fields.clone(),
+                })));
+                out.emit(Instruction::new_u16(
+                    Op::Constant,
+                    out.constants.len() as u16 - 1,
+                ));
+                out.define_value(&s.name);
+            }
+            Expr::Field(field) => {
+                field.left.compile(out);
+                out.emit_field(Op::GetField, &field.name);
+            }
+            Expr::Assign(a) => {
+                a.value.compile(out);
+                out.assign(&a.target);
+                //the assignment evaluates to the updated field
+                a.target.compile(out);
+            }
+            //the value is compiled first so it still sees a name it shadows
+            Expr::Let(l) if l.constant => {
+                let inline = match out.fold(&l.value) {
+                    Some(value) => {
+                        out.constants.push(value);
+                        let index = out.constants.len() as u16 - 1;
+                        out.emit(Instruction::new_u16(Op::Constant, index));
+                        Some(index)
+                    }
+                    None => {
+                        l.value.compile(out);
+                        None
+                    }
+                };
+                out.define_const(&l.name, inline);
+            }
             Expr::Let(l) => {
-                let symbol = out.symbols.define(l.name.clone());
                 l.value.compile(out);
-
-                let op = if symbol.scope == SymbolScope::Global {
-                    Op::SetGlobal
-                } else {
-                    Op::SetLocal
-                };
-                out.emit(Instruction::new_u16(op, symbol.index));
+                out.define_value(&l.name);
             }
             Expr::Identifier(i) => {
-                let symbol = out.symbols.resolve(i).expect("Undefined variable");
-                out.emit_symbol(symbol);
+                match out.symbols.resolve(i) {
+                    Some(symbol) => out.emit_symbol(symbol),
+                    None => {
+                        out.errors.push(format!("Undefined variable {}", i));
+                        //keeps the stack balanced, the program won't run anyway
+                        out.emit(Instruction::new(Op::Null));
+                    }
+                }
             }
+            Expr::Array(a) if has_spread(a) => out.spread_array(a),
             Expr::Array(a) => {
                 for elem in a {
                     elem.compile(out);
                 }
                 out.emit(Instruction::new_u16(Op::Array, a.len() as u16));
             }
-            Expr::Hash(h) => {
-                for (key, value) in h {
-                    key.compile(out);
-                    value.compile(out);
+            Expr::Spread(_) => unreachable!("Spread outside of an array or call"),
+            Expr::Tuple(t) => {
+                for elem in t {
+                    elem.compile(out);
+                }
+                out.emit(Instruction::new_u16(Op::Tuple, t.len() as u16));
+            }
+            Expr::Destructure(d) => {
+                d.value.compile(out);
+                out.emit(Instruction::new_u16(Op::Unpack, d.names.len() as u16));
+                for name in &d.names {
+                    out.define_value(name);
                 }
-                out.emit(Instruction::new_u16(Op::Hash, h.len() as u16));
             }
+            Expr::Hash(h) => out.spread_hash(h),
             Expr::Return(e) => {
-                e.compile(out);
+                out.compile_tail(e);
                 out.emit(Instruction::new(Op::ReturnVal));
             }
+            Expr::Call(c) if has_spread(&c.args) => {
+                c.expr.compile(out);
+                out.spread_array(&c.args);
+                out.emit(Instruction::new(Op::CallSpread));
+            }
             Expr::Call(c) => {
                 c.expr.compile(out);
                 for arg in &c.args {
@@ -1,25 +1,58 @@
-use super::types::{Binding, Builtin, Closure, Primitive};
-use crate::monkey::vm::types::Op;
+use super::{
+    compiler::{Compilation, CompiledContext},
+    types::{Binding, Builtin, Channel, Closure, Primitive, Record},
+};
+use crate::monkey::{
+    indexing::{normalize_index, slice_bounds},
+    prelude,
+    rng::{Rng, DEFAULT_SEED},
+    stdlib::{equal, text, Clock, Context, SystemClock, Value},
+    vm::types::Op,
+};
 use std::{
+    cell::RefCell,
     collections::{HashMap, VecDeque},
     fmt::Write,
+    rc::Rc,
 };
 
+//Where to resume when something is thrown inside a try block
+#[derive(Debug)]
+struct Handler {
+    catch_ip: usize,
+    sp: usize,
+}
+
 #[derive(Debug)]
 struct Frame {
     closure: Closure,
     ip: usize,
     base: usize,
+    //number of named args the caller actually passed
+    num_args: usize,
+    handlers: Vec<Handler>,
+    //set while the frame runs the body of a generator
+    generator: Option<Rc<RefCell<Generator>>>,
 }
 
 impl Frame {
-    fn new(closure: Closure, base: usize) -> Self {
+    fn new(closure: Closure, base: usize, num_args: usize) -> Self {
         Self {
             closure,
             ip: 0,
             base,
+            num_args,
+            handlers: Vec::new(),
+            generator: None,
         }
     }
+    //Moves the frame to a new base, handler stack pointers move along
+    fn rebase(&mut self, base: usize) {
+        for handler in &mut self.handlers {
+            handler.sp = handler.sp - self.base + base;
+        }
+        self.base = base;
+    }
     fn next(&mut self) -> u8 {
         if self.ip < self.closure.fn_.body.len() {
             let ret = self.closure.fn_.body[self.ip];
@@ -43,223 +76,699 @@ impl Frame {
     }
 }
 
+//A suspended generator call, its frame and stack slice are saved on yield
+//and put back on top of the caller when `next` resumes it
+#[derive(Debug)]
+pub struct Generator {
+    //`None` once the body has finished
+    frame: Option<Frame>,
+    stack: Vec<Binding>,
+    running: bool,
+}
+
+//Spawned tasks get a smaller stack than the main one
+const TASK_STACK_SIZE: usize = 4096;
+//Instructions a task runs before the scheduler may switch to another one
+const TIME_SLICE: usize = 100;
+
+//A green thread that isn't running, the running one lives in the machine
+#[derive(Debug)]
+struct Task {
+    //0 is the main program
+    id: usize,
+    stack: Vec<Binding>,
+    sp: usize,
+    frames: Vec<Frame>,
+    //channel the task is blocked receiving from, the value is pushed for it
+    //when it runs again
+    waiting: Option<Rc<RefCell<Channel>>>,
+}
+
+impl Task {
+    fn runnable(&self) -> bool {
+        match &self.waiting {
+            Some(chan) => !chan.borrow().queue.is_empty(),
+            None => true,
+        }
+    }
+}
+
 pub struct Machine {
     stack: Vec<Binding>,
     sp: usize,
     globals: Vec<Binding>,
     frames: Vec<Frame>,
     stdout: String,
+    //id of the running task
+    task: usize,
+    next_task: usize,
+    tasks: VecDeque<Task>,
+    //the main task once it has run out of code, other tasks may still run
+    main: Option<Task>,
+    rng: Rng,
+    //for random builtins, apart from the scheduler's so their values don't
+    //depend on how tasks were scheduled
+    random: Rng,
+    steps: usize,
+    constants: Rc<[Primitive]>,
+    //natives running a callback, tasks can't switch while there are any
+    natives: usize,
+    clock: Rc<dyn Clock>,
 }
 
 impl Default for Machine {
     fn default() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+}
+
+fn error(msg: &str) -> Binding {
+    Binding::Error(msg.to_string())
+}
+
+impl Machine {
+    //The seed decides the order tasks are scheduled in and what random
+    //builtins give
+    pub fn with_seed(seed: u64) -> Self {
         Self {
             sp: 0,
             stack: vec![Binding::Null; u16::MAX as usize],
             globals: vec![Binding::Null; u16::MAX as usize],
             frames: Vec::new(),
             stdout: String::new(),
+            task: 0,
+            next_task: 1,
+            tasks: VecDeque::new(),
+            main: None,
+            rng: Rng::new(seed),
+            random: Rng::new(seed),
+            steps: 0,
+            constants: Rc::new([]),
+            natives: 0,
+            clock: Rc::new(SystemClock),
         }
     }
-}
-
-impl Machine {
-    pub fn run(&mut self, constants: Vec<Primitive>, closure: Closure) {
+    //Replaces the clock `now` reads, a fixed one keeps output the same
+    pub fn with_clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    //Compiles the prelude into `ctx` and runs it so its globals are set,
+    //user code compiled into the same context can then use the helpers
+    pub fn load_prelude(&mut self, ctx: &mut CompiledContext) {
+        prelude::program().compile(ctx);
+        let errors = ctx.take_errors();
+        assert!(errors.is_empty(), "Prelude should compile: {:?}", errors);
+        self.run(ctx.get_constants(), ctx.make_main_closure())
+            .expect("Prelude should run");
+        ctx.clear_instructions();
+        //time slices count from the user code, not from the prelude
+        self.steps = 0;
+    }
+    //Runs the main closure, anything thrown and not caught is returned
+    pub fn run(&mut self, constants: Vec<Primitive>, closure: Closure) -> Result<(), Binding> {
         println!("Constants: {:?}", constants);
+        self.constants = constants.into();
+        let constants = self.constants.clone();
 
-        let main_frame = Frame::new(closure, 0);
+        let main_frame = Frame::new(closure, 0, 0);
         self.frames.push(main_frame);
 
-        while let Some(frame) = self.frames.last() {
-            if !frame.valid_pos() {
-                break;
+        loop {
+            let result = if !matches!(self.frames.last(), Some(frame) if frame.valid_pos()) {
+                match self.finish_task() {
+                    Ok(true) => Ok(()),
+                    Ok(false) => break,
+                    Err(thrown) => Err(thrown),
+                }
+            } else {
+                let op = Op::from(self.frame().next());
+                self.execute(op, &constants)
+            };
+            if let Err(thrown) = result {
+                if let Err(uncaught) = self.throw(thrown, 0) {
+                    if let Some(main) = self.main.take() {
+                        self.swap_task(main);
+                    }
+                    self.tasks.clear();
+                    self.frames.clear();
+                    self.sp = 0;
+                    return Err(uncaught);
+                }
             }
-            let op = Op::from(self.frame().next());
-            match op {
-                Op::Add
-                | Op::Sub
-                | Op::Mul
-                | Op::Div
-                | Op::Eq
-                | Op::Neq
-                | Op::Lt
-                | Op::Index
-                | Op::Mod => {
-                    self.binary_op(op);
-                }
-                Op::Bang => {
-                    let pref = self.pop().clone();
-                    match pref {
-                        Binding::Primitive(Primitive::Bool(b)) => {
-                            self.push(Primitive::Bool(!b).into());
-                        }
-                        Binding::Primitive(Primitive::Int(i)) => {
-                            self.push(Primitive::Bool(i != 0).into());
-                        }
-                        Binding::Null => {
-                            self.push(Primitive::Bool(true).into());
-                        }
-                        _ => panic!("Invalid types for bang"),
+            self.steps += 1;
+            if self.steps == TIME_SLICE {
+                self.steps = 0;
+                self.switch_task(None);
+            }
+        }
+        Ok(())
+    }
+
+    //Puts `task` in the machine and gives back the one that was running
+    fn swap_task(&mut self, mut task: Task) -> Task {
+        std::mem::swap(&mut self.stack, &mut task.stack);
+        std::mem::swap(&mut self.sp, &mut task.sp);
+        std::mem::swap(&mut self.frames, &mut task.frames);
+        std::mem::swap(&mut self.task, &mut task.id);
+        task
+    }
+
+    //Parks the running task and switches to a runnable one, picked with the
+    //seeded rng so the order is the same on every run. Returns false and
+    //keeps running the current task when there is no other
+    fn switch_task(&mut self, waiting: Option<Rc<RefCell<Channel>>>) -> bool {
+        if self.natives > 0 {
+            return false;
+        }
+        let runnable = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.runnable())
+            .map(|(ix, _)| ix)
+            .collect::<Vec<_>>();
+        if runnable.is_empty() {
+            return false;
+        }
+        let ix = runnable[self.rng.below(runnable.len())];
+        let mut next = self.tasks.remove(ix).unwrap();
+        let received = next.waiting.take();
+        let mut current = self.swap_task(next);
+        current.waiting = waiting;
+        self.tasks.push_back(current);
+        if let Some(chan) = received {
+            //the task took the call to recv off its stack before waiting, so
+            //the value has room
+            let value = chan.borrow_mut().queue.pop_front().unwrap();
+            self.push(value).expect("No room for the received value");
+        }
+        true
+    }
+
+    //The running task is out of code, main is kept for its last value while
+    //the rest of the tasks finish. Returns false once nothing can run
+    fn finish_task(&mut self) -> Result<bool, Binding> {
+        let finished = self.task;
+        if self.switch_task(None) {
+            //the finished task was parked at the back
+            let done = self.tasks.pop_back().unwrap();
+            if finished == 0 {
+                self.main = Some(done);
+            }
+            return Ok(true);
+        }
+        if finished == 0 {
+            self.tasks.clear();
+            return Ok(false);
+        }
+        if let Some(main) = self.main.take() {
+            self.swap_task(main);
+            self.tasks.clear();
+            return Ok(false);
+        }
+        //main is still waiting on a channel nobody is left to send on
+        let ix = self.tasks.iter().position(|task| task.id == 0).unwrap();
+        let main = self.tasks.remove(ix).unwrap();
+        self.swap_task(main);
+        Err(error("Deadlock, every task is waiting on a channel"))
+    }
+
+    //Unwinds to the closest handler, dropping frames that have none. Frames
+    //below `floor` belong to code waiting on a native and are left alone
+    fn throw(&mut self, thrown: Binding, floor: usize) -> Result<(), Binding> {
+        while self.frames.len() > floor {
+            let frame = self.frames.last_mut().unwrap();
+            if let Some(handler) = frame.handlers.pop() {
+                frame.set_exec(handler.catch_ip);
+                self.sp = handler.sp;
+                return self.push(thrown);
+            }
+            let frame = self.frames.pop().unwrap();
+            //an uncaught throw finishes the generator
+            if let Some(gen) = frame.generator {
+                gen.borrow_mut().running = false;
+            }
+        }
+        Err(thrown)
+    }
+
+    //Puts a suspended generator frame back on top of the stack, the result of
+    //`next` is whatever it yields or null once it is done
+    fn resume(&mut self, gen: Rc<RefCell<Generator>>) -> Result<(), Binding> {
+        let mut state = gen.borrow_mut();
+        if state.running {
+            return Err(error("Generator is already running"));
+        }
+        if state.frame.is_some() && self.sp + 1 + state.stack.len() > self.stack.len() {
+            return Err(error("Stack overflow"));
+        }
+        let Some(mut frame) = state.frame.take() else {
+            self.push(Binding::Null)?;
+            return Ok(());
+        };
+        state.running = true;
+        //stands in for the callee so returning works like a regular call
+        self.push(Binding::Null)?;
+        frame.rebase(self.sp);
+        for value in state.stack.drain(..) {
+            self.push(value)?;
+        }
+        drop(state);
+        frame.generator = Some(gen);
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn end_frame(&mut self, value: Binding) -> Result<(), Binding> {
+        let frame = self.frames.pop().expect("No frame to return to");
+        self.sp = frame.base - 1;
+        match frame.generator {
+            //returning from a generator just finishes it
+            Some(gen) => {
+                gen.borrow_mut().running = false;
+                self.push(Binding::Null)
+            }
+            None => self.push(value),
+        }
+    }
+
+    fn execute(&mut self, op: Op, constants: &[Primitive]) -> Result<(), Binding> {
+        match op {
+            Op::Add
+            | Op::Sub
+            | Op::Mul
+            | Op::Div
+            | Op::Eq
+            | Op::Neq
+            | Op::Lt
+            | Op::Index
+            | Op::Mod => {
+                self.binary_op(op)?;
+            }
+            Op::Slice => {
+                let end = self.pop().clone();
+                let start = self.pop().clone();
+                let left = self.pop().clone();
+                let bound = |bound: Binding| match bound {
+                    Binding::Primitive(Primitive::Int(i)) => Ok(Some(i)),
+                    Binding::Null => Ok(None),
+                    _ => Err(error("Expected int bounds for slice")),
+                };
+                let (start, end) = (bound(start)?, bound(end)?);
+                match left {
+                    Binding::Array(a) => {
+                        let (start, end) = slice_bounds(start, end, a.len());
+                        self.push(Binding::Array(a[start..end].to_vec()))?;
                     }
+                    Binding::Primitive(Primitive::String_(s)) => {
+                        let chars = s.chars().collect::<Vec<_>>();
+                        let (start, end) = slice_bounds(start, end, chars.len());
+                        self.push(Primitive::String_(chars[start..end].iter().collect()).into())?;
+                    }
+                    _ => return Err(error("Expected array or string for slice")),
                 }
-                Op::Minus => {
-                    let pref = self.pop().clone();
-                    match pref {
-                        Binding::Primitive(Primitive::Int(i)) => {
-                            self.push(Primitive::Int(-i).into());
-                        }
-                        _ => panic!("Invalid types for minus"),
+            }
+            Op::Bang => {
+                let pref = self.pop().clone();
+                match pref {
+                    Binding::Primitive(Primitive::Bool(b)) => {
+                        self.push(Primitive::Bool(!b).into())?;
+                    }
+                    Binding::Primitive(Primitive::Int(i)) => {
+                        self.push(Primitive::Bool(i != 0).into())?;
                     }
+                    Binding::Null => {
+                        self.push(Primitive::Bool(true).into())?;
+                    }
+                    _ => return Err(error("Expected int or bool for ! operator")),
                 }
-                Op::Jmp => {
-                    let ix = self.frame().next_u16();
+            }
+            Op::Minus => {
+                let pref = self.pop().clone();
+                match pref {
+                    Binding::Primitive(Primitive::Int(i)) => {
+                        self.push(Primitive::Int(-i).into())?;
+                    }
+                    _ => return Err(error("Expected int for - operator")),
+                }
+            }
+            Op::Jmp => {
+                let ix = self.frame().next_u16();
+                self.frame().set_exec(ix as usize);
+            }
+            Op::JmpIfFalse => {
+                let ix = self.frame().next_u16();
+                let value = self.pop();
+                let is_truthy = match value {
+                    Binding::Primitive(Primitive::Bool(b)) => *b,
+                    Binding::Primitive(Primitive::Int(i)) => *i != 0,
+                    Binding::Null => true,
+                    _ => return Err(error("Invalid types for JmpIfFalse")),
+                };
+                if !is_truthy {
                     self.frame().set_exec(ix as usize);
                 }
-                Op::JmpIfFalse => {
-                    let ix = self.frame().next_u16();
-                    let value = self.pop();
-                    let is_truthy = match value {
-                        Binding::Primitive(Primitive::Bool(b)) => *b,
-                        Binding::Primitive(Primitive::Int(i)) => *i != 0,
-                        Binding::Null => true,
-                        _ => panic!("Invalid types for JmpIfFalse"),
-                    };
-                    if !is_truthy {
-                        self.frame().set_exec(ix as usize);
-                    }
+            }
+            Op::JmpIfArg => {
+                let arg = self.frame().next_u8() as usize;
+                let ix = self.frame().next_u16();
+                if arg < self.frame().num_args {
+                    self.frame().set_exec(ix as usize);
                 }
-                Op::Call => {
-                    let num_args_called = self.frame().next_u8();
-                    let binding = self.stack[self.sp - 1 - num_args_called as usize].clone();
-                    match binding {
-                        Binding::Closure(closure) => {
-                            let fn_ = closure.fn_;
-                            let num_args = fn_.num_args as usize;
-                            let num_locals = fn_.num_locals as usize;
-                            if num_args_called != num_args as u8 {
-                                panic!(
-                                    "Expected {} args for call but got {}",
-                                    fn_.num_args, num_args_called
-                                );
-                            }
-                            let closure = Closure::new(fn_, closure.free);
-                            let frame = Frame::new(closure, self.sp - num_args);
-                            self.sp = frame.base + num_locals;
-
-                            self.frames.push(frame);
-                        }
-                        Binding::Builtin(builtin) => {
-                            self.builtin_call(builtin, num_args_called);
-                        }
-                        _ => panic!("Expected closure or builtin for call"),
-                    }
+            }
+            Op::Call => {
+                let num_args_called = self.frame().next_u8();
+                self.call(num_args_called)?;
+            }
+            //the finished frame's slots are taken over by the callee and its
+            //args, so whatever it returns goes straight to our caller
+            Op::TailCall => {
+                let num_args_called = self.frame().next_u8() as usize;
+                let frame = self.frames.pop().expect("No frame to tail call from");
+                let start = self.sp - 1 - num_args_called;
+                for i in 0..=num_args_called {
+                    self.stack.swap(frame.base - 1 + i, start + i);
                 }
-                Op::Array => {
-                    let n = self.frame().next_u16();
-                    println!("Array of length {}", n);
-                    let mut array = VecDeque::new();
-                    for _ in 0..n {
-                        array.push_front(self.pop().clone());
+                self.sp = frame.base + num_args_called;
+                self.call(num_args_called as u8)?;
+            }
+            Op::Array => {
+                let n = self.frame().next_u16();
+                println!("Array of length {}", n);
+                let mut array = VecDeque::new();
+                for _ in 0..n {
+                    array.push_front(self.pop().clone());
+                }
+                self.push(Binding::Array(array.into()))?;
+            }
+            //appends the spread array or tuple on top to the array below it
+            Op::Extend => {
+                let elems = self.pop().clone();
+                let elems = match elems {
+                    Binding::Array(a) => a,
+                    elems => match elems.tuple_elems() {
+                        Some(elems) => elems,
+                        None => return Err(error("Expected array or tuple to spread")),
+                    },
+                };
+                match self.pop().clone() {
+                    Binding::Array(mut array) => {
+                        array.extend(elems);
+                        self.push(Binding::Array(array))?;
                     }
-                    self.push(Binding::Array(array.into()));
-                }
-                Op::Hash => {
-                    let n = self.frame().next_u16();
-                    println!("Hash of length {}", n);
-                    let mut hash = HashMap::new();
-                    for _ in 0..n {
-                        let value = self.pop().clone();
-                        let key = self.pop();
-                        if let Binding::Primitive(key) = key {
-                            if !matches!(key, Primitive::Fn(_)) {
-                                hash.insert(key.clone(), value);
-                            }
-                        } else {
-                            panic!("Invalid key for hash");
-                        }
+                    _ => unreachable!(),
+                }
+            }
+            //entries of the hash on top win over the ones below it
+            Op::HashMerge => {
+                let entries = match self.pop().clone() {
+                    Binding::Hash(h) => h,
+                    _ => return Err(error("Expected hash to spread")),
+                };
+                match self.pop().clone() {
+                    Binding::Hash(mut hash) => {
+                        hash.extend(entries);
+                        self.push(Binding::Hash(hash))?;
                     }
-                    self.push(Binding::Hash(hash));
-                }
-                Op::Closure => {
-                    let ix = self.frame().next_u16();
-                    let num_free = self.frame().next_u8() as usize;
-                    let fn_ = constants[ix as usize].clone();
-                    let fn_ = if let Primitive::Fn(fn_) = fn_ {
-                        fn_
-                    } else {
-                        panic!("Expected Function literal for closure");
+                    _ => unreachable!(),
+                }
+            }
+            //the args come as an array built with Extend
+            Op::CallSpread => {
+                let args = match self.pop().clone() {
+                    Binding::Array(a) => a,
+                    _ => unreachable!(),
+                };
+                if args.len() > u8::MAX as usize {
+                    return Err(error("Too many arguments for function call"));
+                }
+                let num_args_called = args.len() as u8;
+                for arg in args {
+                    self.push(arg)?;
+                }
+                self.call(num_args_called)?;
+            }
+            Op::Tuple => {
+                let n = self.frame().next_u16() as usize;
+                let elems = self.stack[self.sp - n..self.sp].to_vec();
+                self.sp -= n;
+                self.push(Binding::tuple(elems))?;
+            }
+            //leaves the elements on the stack with the first one on top
+            Op::Unpack => {
+                let n = self.frame().next_u16() as usize;
+                let elems = match self.pop().clone().tuple_elems() {
+                    Some(elems) if elems.len() == n => elems,
+                    Some(_) => return Err(error("Wrong number of names to destructure tuple")),
+                    None => return Err(error("Expected tuple to destructure")),
+                };
+                for elem in elems.into_iter().rev() {
+                    self.push(elem)?;
+                }
+            }
+            Op::Concat => {
+                let n = self.frame().next_u16() as usize;
+                let mut string = String::new();
+                for part in &self.stack[self.sp - n..self.sp] {
+                    string.push_str(&text(part));
+                }
+                self.sp -= n;
+                self.push(Primitive::String_(string).into())?;
+            }
+            Op::Hash => {
+                let n = self.frame().next_u16();
+                println!("Hash of length {}", n);
+                let mut hash = HashMap::new();
+                for _ in 0..n {
+                    let value = self.pop().clone();
+                    match self.pop().to_key() {
+                        Some(key) => hash.insert(key, value),
+                        None => return Err(error("Invalid key for hash")),
                     };
-                    let mut free = Vec::new();
-                    for i in 0..num_free {
-                        free.push(self.stack[self.sp - num_free + i].clone());
-                    }
-                    self.sp -= num_free;
-                    let closure = Closure::new(fn_, free);
-                    self.push(Binding::Closure(closure));
                 }
-                Op::Null => {
-                    self.push(Binding::Null);
+                self.push(Binding::Hash(hash))?;
+            }
+            Op::Closure => {
+                let ix = self.frame().next_u16();
+                let num_free = self.frame().next_u8() as usize;
+                let fn_ = constants[ix as usize].clone();
+                let fn_ = if let Primitive::Fn(fn_) = fn_ {
+                    fn_
+                } else {
+                    panic!("Expected Function literal for closure");
+                };
+                let mut free = Vec::new();
+                for i in 0..num_free {
+                    free.push(self.stack[self.sp - num_free + i].clone());
                 }
-                Op::Constant => {
-                    let ix = self.frame().next_u16();
-                    self.push(Binding::Primitive(constants[ix as usize].clone()));
+                self.sp -= num_free;
+                let closure = Closure::new(fn_, free);
+                self.push(Binding::Closure(closure))?;
+            }
+            Op::GetField | Op::SetField => {
+                let ix = self.frame().next_u16() as usize;
+                let slot = self.frame().next_u8() as usize;
+                let name = match &constants[ix] {
+                    Primitive::String_(name) => name,
+                    _ => panic!("Expected field name constant"),
+                };
+                let mut record = match self.pop().clone() {
+                    Binding::Record(record) => record,
+                    _ => return Err(error("Expected record for field access")),
+                };
+                let Some(slot) = record.def.slot(name, slot) else {
+                    return Err(error("Unknown field for record"));
+                };
+                if op == Op::GetField {
+                    self.push(record.fields[slot].clone())?;
+                } else {
+                    record.fields[slot] = self.pop().clone();
+                    self.push(Binding::Record(record))?;
                 }
-                Op::Pop => {
-                    let value = self.pop();
-                    println!("Popped {:?} from stack", value);
+            }
+            Op::Try => {
+                let catch_ip = self.frame().next_u16() as usize;
+                let sp = self.sp;
+                self.frame().handlers.push(Handler { catch_ip, sp });
+            }
+            Op::EndTry => {
+                self.frame().handlers.pop();
+            }
+            Op::Throw => {
+                let thrown = self.pop().clone();
+                return Err(thrown);
+            }
+            //resumes the generator on top of the stack, leaving it below the
+            //value it gives
+            Op::Resume => match self.stack[self.sp - 1].clone() {
+                Binding::Generator(gen) => self.resume(gen)?,
+                _ => return Err(error("Expected generator for yield*")),
+            },
+            Op::JmpIfDone => {
+                let ix = self.frame().next_u16();
+                let done = match &self.stack[self.sp - 2] {
+                    Binding::Generator(gen) => gen.borrow().frame.is_none(),
+                    _ => panic!("Expected generator below its value"),
+                };
+                if done {
+                    self.sp -= 2;
+                    self.frame().set_exec(ix as usize);
                 }
-                Op::True => {
-                    self.push(Primitive::Bool(true).into());
+            }
+            Op::Yield => {
+                let value = self.pop().clone();
+                let mut frame = self.frames.pop().expect("No frame to yield from");
+                let gen = frame
+                    .generator
+                    .take()
+                    .expect("Yield outside of a generator");
+                let stack = self.stack[frame.base..self.sp].to_vec();
+                self.sp = frame.base - 1;
+                frame.rebase(0);
+                self.push(value)?;
+                let mut state = gen.borrow_mut();
+                state.frame = Some(frame);
+                state.stack = stack;
+                state.running = false;
+            }
+            Op::Null => {
+                self.push(Binding::Null)?;
+            }
+            Op::Constant => {
+                let ix = self.frame().next_u16();
+                self.push(Binding::Primitive(constants[ix as usize].clone()))?;
+            }
+            Op::Pop => {
+                let value = self.pop();
+                println!("Popped {:?} from stack", value);
+            }
+            Op::True => {
+                self.push(Primitive::Bool(true).into())?;
+            }
+            Op::False => {
+                self.push(Primitive::Bool(false).into())?;
+            }
+            Op::SetGlobal => {
+                let ix = self.frame().next_u16();
+                self.globals[ix as usize] = self.pop().clone();
+            }
+            Op::SetLocal => {
+                let ix = self.frame().next_u16() as usize;
+                let base = self.frame().base;
+                let value = self.pop().clone();
+                self.stack[base + ix] = value;
+            }
+            Op::GetGlobal => {
+                let ix = self.frame().next_u16();
+                if let Binding::Uninit(name) = &self.globals[ix as usize] {
+                    return Err(error(&format!("{} is used before it is defined", name)));
                 }
-                Op::False => {
-                    self.push(Primitive::Bool(false).into());
+                self.push(self.globals[ix as usize].clone())?;
+            }
+            Op::DeclareGlobal => {
+                let ix = self.frame().next_u16() as usize;
+                let name = self.frame().next_u16() as usize;
+                match &constants[name] {
+                    Primitive::String_(name) => self.globals[ix] = Binding::Uninit(name.clone()),
+                    _ => panic!("Expected global name constant"),
                 }
-                Op::SetGlobal => {
-                    let ix = self.frame().next_u16();
-                    self.globals[ix as usize] = self.pop().clone();
+            }
+            Op::GetLocal => {
+                let ix = self.frame().next_u16() as usize;
+                let base = self.frame().base;
+                self.push(self.stack[base + ix].clone())?;
+            }
+            Op::GetBuiltin => {
+                let builtin = Builtin::from(self.frame().next_u8());
+                println!("Builtin: {}", builtin);
+                self.push(Binding::Builtin(builtin))?;
+            }
+            Op::GetFree => {
+                let ix = self.frame().next_u8() as usize;
+                let val = self.frame().closure.free[ix].clone();
+                self.push(val)?;
+            }
+            Op::CurrentClosure => {
+                let closure = self.frame().closure.clone();
+                self.push(Binding::Closure(closure))?;
+            }
+            Op::ReturnVal => {
+                let val = self.pop().clone();
+                self.end_frame(val)?;
+            }
+            Op::Return => {
+                self.end_frame(Binding::Null)?;
+            }
+        }
+        Ok(())
+    }
+
+    //Calls the callee sitting below the args on top of the stack
+    fn call(&mut self, num_args_called: u8) -> Result<(), Binding> {
+        let binding = self.stack[self.sp - 1 - num_args_called as usize].clone();
+        match binding {
+            Binding::Closure(closure) => {
+                let fn_ = &closure.fn_;
+                let called = num_args_called as usize;
+                let num_args = fn_.num_args as usize;
+                let num_locals = fn_.num_locals as usize;
+                if called < fn_.num_required as usize || (!fn_.variadic && called > num_args) {
+                    return Err(error(&format!(
+                        "Expected {} args for call but got {}",
+                        fn_.num_args, num_args_called
+                    )));
                 }
-                Op::SetLocal => {
-                    let ix = self.frame().next_u16() as usize;
-                    let base = self.frame().base;
-                    let value = self.pop().clone();
-                    self.stack[base + ix] = value;
-                }
-                Op::GetGlobal => {
-                    let ix = self.frame().next_u16();
-                    self.push(self.globals[ix as usize].clone());
-                }
-                Op::GetLocal => {
-                    let ix = self.frame().next_u16() as usize;
-                    let base = self.frame().base;
-                    self.push(self.stack[base + ix].clone());
-                }
-                Op::GetBuiltin => {
-                    let builtin = Builtin::from(self.frame().next_u8());
-                    println!("Builtin: {}", builtin);
-                    self.push(Binding::Builtin(builtin));
-                }
-                Op::GetFree => {
-                    let ix = self.frame().next_u8() as usize;
-                    let val = self.frame().closure.free[ix].clone();
-                    self.push(val);
-                }
-                Op::CurrentClosure => {
-                    let closure = self.frame().closure.clone();
-                    self.push(Binding::Closure(closure));
-                }
-                Op::ReturnVal => {
-                    let val = self.pop().clone();
-                    let frame = self.frames.pop().expect("No frame to return to");
-                    self.sp = frame.base - 1;
-                    self.push(val);
+                //Pack the extra args into the rest parameter and
+                //leave a slot for each missing optional one
+                let rest = if fn_.variadic && called > num_args {
+                    let rest = self.stack[self.sp - (called - num_args)..self.sp].to_vec();
+                    self.sp -= called - num_args;
+                    rest
+                } else {
+                    Vec::new()
+                };
+                for _ in called..num_args {
+                    self.push(Binding::Null)?;
+                }
+                let num_params = if fn_.variadic {
+                    self.push(Binding::Array(rest))?;
+                    num_args + 1
+                } else {
+                    num_args
+                };
+                let frame = Frame::new(closure, self.sp - num_params, called.min(num_args));
+                if frame.base + num_locals > self.stack.len() {
+                    return Err(error("Stack overflow"));
                 }
-                Op::Return => {
-                    let frame = self.frames.pop().expect("No frame to return to");
+                self.sp = frame.base + num_locals;
+
+                if frame.closure.fn_.generator {
+                    let stack = self.stack[frame.base..self.sp].to_vec();
                     self.sp = frame.base - 1;
-                    self.push(Binding::Null);
+                    self.push(Binding::Generator(Rc::new(RefCell::new(Generator {
+                        frame: Some(frame),
+                        stack,
+                        running: false,
+                    }))))?;
+                } else {
+                    self.frames.push(frame);
+                }
+            }
+            Binding::Builtin(builtin) => {
+                self.builtin_call(builtin, num_args_called)?;
+            }
+            Binding::Primitive(Primitive::Struct(def)) => {
+                if num_args_called as usize != def.fields.len() {
+                    return Err(error("Wrong number of fields for struct constructor"));
                 }
+                let fields = self.stack[self.sp - num_args_called as usize..self.sp].to_vec();
+                self.sp -= num_args_called as usize + 1;
+                self.push(Binding::Record(Record { def, fields }))?;
             }
+            _ => return Err(error("Expected closure or builtin for call")),
         }
+        Ok(())
     }
 
     fn pop(&mut self) -> &Binding {
@@ -267,9 +776,13 @@ impl Machine {
         &self.stack[self.sp]
     }
 
-    fn push(&mut self, binding: Binding) {
+    fn push(&mut self, binding: Binding) -> Result<(), Binding> {
+        if self.sp == self.stack.len() {
+            return Err(error("Stack overflow"));
+        }
         self.stack[self.sp] = binding;
         self.sp += 1;
+        Ok(())
     }
 
     fn frame(&mut self) -> &mut Frame {
@@ -285,62 +798,90 @@ impl Machine {
             _ => panic!("Expected only one expression on stack"),
         }
     }
-    fn binary_op(&mut self, op: Op) {
+    fn binary_op(&mut self, op: Op) -> Result<(), Binding> {
         let right = self.pop().clone();
         let left = self.pop().clone();
         match (left, right) {
             (Binding::Primitive(left), Binding::Primitive(right)) => match (left, right) {
                 (Primitive::Int(l), Primitive::Int(r)) => match op {
-                    Op::Add => self.push(Primitive::Int(l + r).into()),
-                    Op::Sub => self.push(Primitive::Int(l - r).into()),
-                    Op::Mul => self.push(Primitive::Int(l * r).into()),
-                    Op::Div => self.push(Primitive::Int(l / r).into()),
-                    Op::Eq => self.push(Primitive::Bool(l == r).into()),
-                    Op::Neq => self.push(Primitive::Bool(l != r).into()),
-                    Op::Lt => self.push(Primitive::Bool(l < r).into()),
-                    Op::Mod => self.push(Primitive::Int(l % r).into()),
-                    _ => panic!("Invalid op for ints"),
+                    Op::Add => self.push(Primitive::Int(l + r).into())?,
+                    Op::Sub => self.push(Primitive::Int(l - r).into())?,
+                    Op::Mul => self.push(Primitive::Int(l * r).into())?,
+                    Op::Div | Op::Mod if r == 0 => return Err(error("Division by zero")),
+                    Op::Div => self.push(Primitive::Int(l / r).into())?,
+                    Op::Eq => self.push(Primitive::Bool(l == r).into())?,
+                    Op::Neq => self.push(Primitive::Bool(l != r).into())?,
+                    Op::Lt => self.push(Primitive::Bool(l < r).into())?,
+                    Op::Mod => self.push(Primitive::Int(l % r).into())?,
+                    _ => return Err(error("Invalid op for ints")),
                 },
                 (Primitive::Bool(l), Primitive::Bool(r)) => match op {
-                    Op::Eq => self.push(Primitive::Bool(l == r).into()),
-                    Op::Neq => self.push(Primitive::Bool(l != r).into()),
-                    _ => panic!("Invalid op for bools"),
+                    Op::Eq => self.push(Primitive::Bool(l == r).into())?,
+                    Op::Neq => self.push(Primitive::Bool(l != r).into())?,
+                    _ => return Err(error("Invalid op for bools")),
                 },
                 (Primitive::String_(l), Primitive::String_(r)) => match op {
-                    Op::Eq => self.push(Primitive::Bool(l == r).into()),
-                    Op::Neq => self.push(Primitive::Bool(l != r).into()),
-                    Op::Add => self.push(Primitive::String_(format!("{}{}", l, r)).into()),
-                    _ => panic!("Invalid op for string"),
+                    Op::Eq => self.push(Primitive::Bool(l == r).into())?,
+                    Op::Neq => self.push(Primitive::Bool(l != r).into())?,
+                    Op::Add => self.push(Primitive::String_(format!("{}{}", l, r)).into())?,
+                    _ => return Err(error("Invalid op for string")),
+                },
+                (Primitive::String_(l), Primitive::Int(r)) => match op {
+                    Op::Index => {
+                        let chars = l.chars().collect::<Vec<_>>();
+                        match normalize_index(r, chars.len()) {
+                            Some(ix) => self.push(Primitive::String_(chars[ix].into()).into())?,
+                            None => return Err(error("Index out of bounds for string")),
+                        }
+                    }
+                    _ => return Err(error("Invalid op for string")),
                 },
-                _ => panic!("Invalid types for binary op",),
+                (Primitive::Tuple(l), Primitive::Int(r)) => match op {
+                    Op::Index => match normalize_index(r, l.len()) {
+                        Some(ix) => self.push(l[ix].clone().into())?,
+                        None => return Err(error("Index out of bounds for tuple")),
+                    },
+                    _ => return Err(error("Invalid op for tuple")),
+                },
+                _ => return Err(error("Invalid types for binary op")),
+            },
+            (Binding::Tuple(l), Binding::Primitive(Primitive::Int(r))) => match op {
+                Op::Index => match normalize_index(r, l.len()) {
+                    Some(ix) => self.push(l[ix].clone())?,
+                    None => return Err(error("Index out of bounds for tuple")),
+                },
+                _ => return Err(error("Invalid op for tuple")),
             },
             (Binding::Array(l), Binding::Primitive(Primitive::Int(r))) => match op {
-                Op::Index => {
-                    if r < 0 {
-                        panic!("Expected positive index for array");
-                    } else if r as usize >= l.len() {
-                        panic!("Expected index less than array length");
-                    } else {
-                        self.push(l[r as usize].clone());
-                    }
-                }
-                _ => panic!("Invalid op for array"),
+                Op::Index => match normalize_index(r, l.len()) {
+                    Some(ix) => self.push(l[ix].clone())?,
+                    None => return Err(error("Index out of bounds for array")),
+                },
+                _ => return Err(error("Invalid op for array")),
             },
             (Binding::Hash(l), Binding::Primitive(key)) => match op {
                 Op::Index => {
                     if let Some(key) = l.get(&key) {
-                        self.stack.push(key.clone());
+                        self.push(key.clone())?;
                     } else {
-                        panic!("Invalid key for hash");
+                        return Err(error("Invalid key for hash"));
                     }
                 }
-                _ => panic!("Invalid op for hash"),
+                _ => return Err(error("Invalid op for hash")),
             },
-            _ => panic!("Invalid types for binary op"),
+            //records are equal when they are of the same struct and their
+            //fields are equal
+            (l @ Binding::Record(_), r @ Binding::Record(_)) => match op {
+                Op::Eq => self.push(Primitive::Bool(equal(&l, &r)).into())?,
+                Op::Neq => self.push(Primitive::Bool(!equal(&l, &r)).into())?,
+                _ => return Err(error("Expected == or != to compare records")),
+            },
+            _ => return Err(error("Invalid types for binary op")),
         }
+        Ok(())
     }
 
-    fn builtin_call(&mut self, builtin: Builtin, num_args: u8) {
+    fn builtin_call(&mut self, builtin: Builtin, num_args: u8) -> Result<(), Binding> {
         //Gather args
         let mut args = Vec::new();
         for _ in 0..num_args {
@@ -352,77 +893,136 @@ impl Machine {
         match builtin {
             Builtin::Len => {
                 if args.len() != 1 {
-                    panic!("Expected single argument for len builtin");
+                    return Err(error("Expected single argument for len builtin"));
                 }
                 match args.pop().unwrap() {
-                    Binding::Array(a) => self.push(Primitive::Int(a.len() as i64).into()),
-                    Binding::Hash(h) => self.push(Primitive::Int(h.len() as i64).into()),
+                    Binding::Array(a) => self.push(Primitive::Int(a.len() as i64).into())?,
+                    Binding::Hash(h) => self.push(Primitive::Int(h.len() as i64).into())?,
                     Binding::Primitive(Primitive::String_(s)) => {
-                        self.push(Primitive::Int(s.len() as i64).into())
+                        self.push(Primitive::Int(s.chars().count() as i64).into())?
                     }
-                    _ => panic!("Expected array, hash or string for len builtin"),
+                    _ => return Err(error("Expected array, hash or string for len builtin")),
                 }
             }
-            Builtin::First => {
-                if args.len() != 1 {
-                    panic!("Expected single argument for first builtin");
-                }
-                match args.pop().unwrap() {
-                    Binding::Array(a) => {
-                        if let Some(first) = a.first() {
-                            self.push(first.clone());
-                        } else {
-                            self.push(Binding::Null);
-                        }
-                    }
-                    _ => panic!("Expected array for first builtin"),
+            Builtin::Puts => {
+                while let Some(arg) = args.pop() {
+                    self.stdout
+                        .write_fmt(format_args!("{}\n", text(&arg)))
+                        .unwrap();
                 }
+                self.push(Binding::Null)?;
             }
-            Builtin::Last => {
-                if args.len() != 1 {
-                    panic!("Expected single argument for last builtin");
-                }
-                match args.pop().unwrap() {
-                    Binding::Array(a) => {
-                        if let Some(last) = a.last() {
-                            self.push(last.clone());
-                        } else {
-                            self.push(Binding::Null);
-                        }
+            Builtin::Next => match args.pop() {
+                Some(Binding::Generator(gen)) if args.is_empty() => self.resume(gen)?,
+                _ => return Err(error("Expected generator for next builtin")),
+            },
+            Builtin::Spawn => match args.pop() {
+                Some(Binding::Closure(closure)) if args.is_empty() => {
+                    let fn_ = &closure.fn_;
+                    if fn_.num_required > 0 || fn_.generator {
+                        return Err(error("Expected function without arguments for spawn"));
                     }
-                    _ => panic!("Expected array for last builtin"),
-                }
-            }
-            Builtin::Rest => {
-                if args.len() != 1 {
-                    panic!("Expected single argument for rest builtin");
-                }
-                match args.pop().unwrap() {
-                    Binding::Array(a) => {
-                        self.push(Binding::Array(a.iter().skip(1).cloned().collect()))
+                    let mut stack = vec![Binding::Null; TASK_STACK_SIZE];
+                    stack[0] = Binding::Closure(closure.clone());
+                    if fn_.variadic {
+                        stack[1 + fn_.num_args as usize] = Binding::Array(Vec::new());
                     }
-                    _ => panic!("Expected array for rest builtin"),
+                    let sp = 1 + fn_.num_locals as usize;
+                    self.tasks.push_back(Task {
+                        id: self.next_task,
+                        stack,
+                        sp,
+                        frames: vec![Frame::new(closure, 1, 0)],
+                        waiting: None,
+                    });
+                    self.next_task += 1;
+                    self.push(Binding::Null)?;
+                }
+                _ => return Err(error("Expected function for spawn builtin")),
+            },
+            Builtin::Chan => {
+                if !args.is_empty() {
+                    return Err(error("Expected no arguments for chan builtin"));
                 }
+                self.push(Binding::Channel(Rc::default()))?;
             }
-            Builtin::Push => {
-                if args.len() != 2 {
-                    panic!("Expected two arguments for push builtin");
+            Builtin::Send => match (args.pop(), args.pop()) {
+                (Some(Binding::Channel(chan)), Some(value)) if args.is_empty() => {
+                    chan.borrow_mut().queue.push_back(value);
+                    self.push(Binding::Null)?;
+                    self.switch_task(None);
                 }
-                match (args.pop().unwrap(), args.pop().unwrap()) {
-                    (Binding::Array(a), new) => {
-                        let mut ret = a.clone();
-                        ret.push(new.clone());
-                        self.push(Binding::Array(ret));
+                _ => return Err(error("Expected channel and value for send builtin")),
+            },
+            Builtin::Recv => match args.pop() {
+                Some(Binding::Channel(chan)) if args.is_empty() => {
+                    let value = chan.borrow_mut().queue.pop_front();
+                    match value {
+                        Some(value) => self.push(value)?,
+                        None => {
+                            if self.natives > 0 {
+                                return Err(error(
+                                    "Can't wait on a channel inside a builtin callback",
+                                ));
+                            }
+                            if !self.switch_task(Some(chan)) {
+                                if self.main.is_none() {
+                                    return Err(error(
+                                        "Deadlock, every task is waiting on a channel",
+                                    ));
+                                }
+                                //main is done and nobody is left to send
+                                self.frames.clear();
+                            }
+                        }
                     }
-                    _ => panic!("Expected array and int or string for push builtin"),
                 }
+                _ => return Err(error("Expected channel for recv builtin")),
+            },
+            Builtin::Native(native) => {
+                args.reverse();
+                let value = native.call(args, self)?;
+                self.push(value)?;
             }
-            Builtin::Puts => {
-                while let Some(arg) = args.pop() {
-                    self.stdout.write_fmt(format_args!("{}\n", arg)).unwrap();
-                }
-                self.push(Binding::Null);
+        }
+        Ok(())
+    }
+}
+
+//A callback runs on top of the native's caller until its frame returns,
+//anything thrown out of it goes back to the native
+impl Context<Binding> for Machine {
+    fn apply(&mut self, f: Binding, args: Vec<Binding>) -> Result<Binding, Binding> {
+        let (depth, sp) = (self.frames.len(), self.sp);
+        let num_args = args.len() as u8;
+        let pushed = std::iter::once(f)
+            .chain(args)
+            .try_for_each(|value| self.push(value));
+        let constants = self.constants.clone();
+        self.natives += 1;
+        let mut result = pushed.and_then(|()| self.call(num_args));
+        while result.is_ok() && self.frames.len() > depth {
+            let op = Op::from(self.frame().next());
+            if let Err(thrown) = self.execute(op, &constants) {
+                result = self.throw(thrown, depth);
+            }
+        }
+        self.natives -= 1;
+        match result {
+            Ok(()) => Ok(self.pop().clone()),
+            Err(thrown) => {
+                self.sp = sp;
+                Err(thrown)
             }
         }
     }
+    fn print(&mut self, text: &str) {
+        self.stdout.push_str(text);
+    }
+    fn now(&mut self) -> i64 {
+        self.clock.now()
+    }
+    fn rng<T>(&mut self, f: impl FnOnce(&mut Rng) -> T) -> T {
+        f(&mut self.random)
+    }
 }
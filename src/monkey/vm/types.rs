@@ -1,4 +1,12 @@
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+use super::machine::Generator;
+use crate::monkey::stdlib::{sorted_entries, Native, Value};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    rc::Rc,
+    str::FromStr,
+};
 
 #[repr(u8)]
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -34,6 +42,24 @@ pub enum Op {
     GetFree = 0x1C,
     CurrentClosure = 0x1D,
     Mod = 0x1E,
+    JmpIfArg = 0x1F,
+    Concat = 0x20,
+    Slice = 0x21,
+    Try = 0x22,
+    EndTry = 0x23,
+    Throw = 0x24,
+    GetField = 0x25,
+    SetField = 0x26,
+    Tuple = 0x27,
+    Unpack = 0x28,
+    Yield = 0x29,
+    Resume = 0x2A,
+    JmpIfDone = 0x2B,
+    TailCall = 0x2C,
+    DeclareGlobal = 0x2D,
+    Extend = 0x2E,
+    HashMerge = 0x2F,
+    CallSpread = 0x30,
 }
 
 impl From<u8> for Op {
@@ -70,6 +96,24 @@ impl From<u8> for Op {
             0x1C => Op::GetFree,
             0x1D => Op::CurrentClosure,
             0x1E => Op::Mod,
+            0x1F => Op::JmpIfArg,
+            0x20 => Op::Concat,
+            0x21 => Op::Slice,
+            0x22 => Op::Try,
+            0x23 => Op::EndTry,
+            0x24 => Op::Throw,
+            0x25 => Op::GetField,
+            0x26 => Op::SetField,
+            0x27 => Op::Tuple,
+            0x28 => Op::Unpack,
+            0x29 => Op::Yield,
+            0x2A => Op::Resume,
+            0x2B => Op::JmpIfDone,
+            0x2C => Op::TailCall,
+            0x2D => Op::DeclareGlobal,
+            0x2E => Op::Extend,
+            0x2F => Op::HashMerge,
+            0x30 => Op::CallSpread,
             _ => panic!("Opcode not found"),
         }
     }
@@ -105,6 +149,16 @@ impl Instruction {
         param.extend(param2.to_be_bytes());
         Self { op, param }
     }
+    pub fn new_u16_u16(op: Op, param1: u16, param2: u16) -> Self {
+        let mut param = param1.to_be_bytes().to_vec();
+        param.extend(param2.to_be_bytes());
+        Self { op, param }
+    }
+    pub fn new_u8_u16(op: Op, param1: u8, param2: u16) -> Self {
+        let mut param = param1.to_be_bytes().to_vec();
+        param.extend(param2.to_be_bytes());
+        Self { op, param }
+    }
     pub fn bytes(self) -> Vec<u8> {
         let mut bytes = Vec::new();
         bytes.push(self.op as u8);
@@ -160,8 +214,45 @@ impl Display for Instruction {
             Op::Array => write!(f, "ARRAY {}", self.param[0]),
             Op::Hash => write!(f, "HASH {}", self.param[0]),
             Op::Index => write!(f, "INDEX"),
+            Op::Slice => write!(f, "SLICE"),
+            Op::Try => write!(
+                f,
+                "TRY {}",
+                u16::from_be_bytes([self.param[0], self.param[1]])
+            ),
+            Op::EndTry => write!(f, "END_TRY"),
+            Op::Throw => write!(f, "THROW"),
+            Op::Yield => write!(f, "YIELD"),
+            Op::Resume => write!(f, "RESUME"),
+            Op::JmpIfDone => write!(
+                f,
+                "JMP_IF_DONE {}",
+                u16::from_be_bytes([self.param[0], self.param[1]])
+            ),
+            Op::GetField => write!(
+                f,
+                "GET_FIELD {} {}",
+                u16::from_be_bytes([self.param[0], self.param[1]]),
+                u8::from_be_bytes([self.param[2]])
+            ),
+            Op::SetField => write!(
+                f,
+                "SET_FIELD {} {}",
+                u16::from_be_bytes([self.param[0], self.param[1]]),
+                u8::from_be_bytes([self.param[2]])
+            ),
             Op::ReturnVal => write!(f, "RETURN_VAL"),
             Op::Call => write!(f, "CALL {}", self.param[0]),
+            Op::TailCall => write!(f, "TAIL_CALL {}", self.param[0]),
+            Op::DeclareGlobal => write!(
+                f,
+                "DECLARE_GLOBAL {} {}",
+                u16::from_be_bytes([self.param[0], self.param[1]]),
+                u16::from_be_bytes([self.param[2], self.param[3]])
+            ),
+            Op::Extend => write!(f, "EXTEND"),
+            Op::HashMerge => write!(f, "HASH_MERGE"),
+            Op::CallSpread => write!(f, "CALL_SPREAD"),
             Op::Return => write!(f, "RETURN"),
             Op::SetLocal => write!(
                 f,
@@ -183,6 +274,27 @@ impl Display for Instruction {
             Op::GetFree => write!(f, "GET_FREE {}", u8::from_be_bytes([self.param[0]])),
             Op::CurrentClosure => write!(f, "CURRENT_CLOSURE"),
             Op::Mod => write!(f, "MOD"),
+            Op::Concat => write!(
+                f,
+                "CONCAT {}",
+                u16::from_be_bytes([self.param[0], self.param[1]])
+            ),
+            Op::Tuple => write!(
+                f,
+                "TUPLE {}",
+                u16::from_be_bytes([self.param[0], self.param[1]])
+            ),
+            Op::Unpack => write!(
+                f,
+                "UNPACK {}",
+                u16::from_be_bytes([self.param[0], self.param[1]])
+            ),
+            Op::JmpIfArg => write!(
+                f,
+                "JMP_IF_ARG {} {}",
+                u8::from_be_bytes([self.param[0]]),
+                u16::from_be_bytes([self.param[1], self.param[2]])
+            ),
         }
     }
 }
@@ -202,22 +314,26 @@ impl Closure {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Builtin {
     Len,
-    First,
-    Last,
-    Rest,
-    Push,
     Puts,
+    Next,
+    Spawn,
+    Chan,
+    Send,
+    Recv,
+    Native(Native),
 }
 
 impl Display for Builtin {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Builtin::Len => write!(f, "len"),
-            Builtin::First => write!(f, "first"),
-            Builtin::Last => write!(f, "last"),
-            Builtin::Rest => write!(f, "rest"),
-            Builtin::Push => write!(f, "push"),
             Builtin::Puts => write!(f, "puts"),
+            Builtin::Next => write!(f, "next"),
+            Builtin::Spawn => write!(f, "spawn"),
+            Builtin::Chan => write!(f, "chan"),
+            Builtin::Send => write!(f, "send"),
+            Builtin::Recv => write!(f, "recv"),
+            Builtin::Native(n) => write!(f, "{}", n.name()),
         }
     }
 }
@@ -227,12 +343,13 @@ impl FromStr for Builtin {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "len" => Ok(Builtin::Len),
-            "first" => Ok(Builtin::First),
-            "last" => Ok(Builtin::Last),
-            "rest" => Ok(Builtin::Rest),
-            "push" => Ok(Builtin::Push),
             "puts" => Ok(Builtin::Puts),
-            _ => Err("Invalid builtin".to_string()),
+            "next" => Ok(Builtin::Next),
+            "spawn" => Ok(Builtin::Spawn),
+            "chan" => Ok(Builtin::Chan),
+            "send" => Ok(Builtin::Send),
+            "recv" => Ok(Builtin::Recv),
+            _ => Native::from_str(s).map(Builtin::Native),
         }
     }
 }
@@ -241,21 +358,83 @@ impl From<u8> for Builtin {
     fn from(i: u8) -> Self {
         match i {
             0 => Builtin::Len,
-            1 => Builtin::First,
-            2 => Builtin::Last,
-            3 => Builtin::Rest,
-            4 => Builtin::Push,
-            5 => Builtin::Puts,
+            1 => Builtin::Puts,
+            2 => Builtin::Next,
+            3 => Builtin::Spawn,
+            4 => Builtin::Chan,
+            5 => Builtin::Send,
+            6 => Builtin::Recv,
+            i if i >= NATIVE_INDEX => {
+                Builtin::Native(Native::from_index(i - NATIVE_INDEX).expect("Invalid builtin"))
+            }
             _ => panic!("Invalid builtin"),
         }
     }
 }
 
+//Natives are numbered from here, leaving room for more builtins of the VM's
+//own before them
+const NATIVE_INDEX: u8 = 64;
+
+impl Builtin {
+    fn arity(&self) -> i64 {
+        match self {
+            Builtin::Chan => 0,
+            Builtin::Len | Builtin::Next | Builtin::Spawn | Builtin::Recv => 1,
+            Builtin::Send => 2,
+            Builtin::Puts => -1,
+            Builtin::Native(native) => native.arity(),
+        }
+    }
+    pub fn index(&self) -> u8 {
+        match self {
+            Builtin::Len => 0,
+            Builtin::Puts => 1,
+            Builtin::Next => 2,
+            Builtin::Spawn => 3,
+            Builtin::Chan => 4,
+            Builtin::Send => 5,
+            Builtin::Recv => 6,
+            Builtin::Native(n) => NATIVE_INDEX + n.index(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CompiledFn {
     pub body: Vec<u8>,
     pub num_locals: u16,
+    //named parameters, including the ones with defaults
     pub num_args: u8,
+    //parameters without a default
+    pub num_required: u8,
+    //extra arguments are collected into an array after the named ones
+    pub variadic: bool,
+    //calls return a generator that runs the body on `next`
+    pub generator: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl StructDef {
+    //`slot` is the compiler's guess, it's only trusted if the name matches
+    pub fn slot(&self, name: &str, slot: usize) -> Option<usize> {
+        if self.fields.get(slot).is_some_and(|f| f == name) {
+            Some(slot)
+        } else {
+            self.fields.iter().position(|f| f == name)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub def: Rc<StructDef>,
+    pub fields: Vec<Binding>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -264,6 +443,9 @@ pub enum Primitive {
     String_(String),
     Bool(bool),
     Fn(CompiledFn),
+    Struct(Rc<StructDef>),
+    //tuples of primitives, so they can be used as hash keys
+    Tuple(Vec<Primitive>),
 }
 
 impl Display for Primitive {
@@ -279,10 +461,27 @@ impl Display for Primitive {
                 }
                 write!(f, "}}")
             }
+            Primitive::Struct(s) => write!(f, "struct {} {{ {} }}", s.name, s.fields.join(", ")),
+            Primitive::Tuple(t) => write_tuple(f, t),
         }
     }
 }
 
+fn write_tuple<T: Display>(f: &mut std::fmt::Formatter<'_>, elems: &[T]) -> std::fmt::Result {
+    match elems {
+        [elem] => write!(f, "({},)", elem),
+        _ => write!(
+            f,
+            "({})",
+            elems
+                .iter()
+                .map(|e| format!("{}", e))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
 impl From<Primitive> for Binding {
     fn from(p: Primitive) -> Self {
         Binding::Primitive(p)
@@ -297,40 +496,204 @@ pub enum Binding {
     Null,
     Builtin(Builtin),
     Closure(Closure),
+    //runtime errors, catchable like any thrown value
+    Error(String),
+    Record(Record),
+    //tuples holding non primitive values
+    Tuple(Vec<Binding>),
+    Generator(Rc<RefCell<Generator>>),
+    Channel(Rc<RefCell<Channel>>),
+    //a hoisted global read before its let ran
+    Uninit(String),
+}
+
+//Values sent and not received yet, sending never blocks
+#[derive(Debug, Default)]
+pub struct Channel {
+    pub queue: VecDeque<Binding>,
+}
+
+impl Binding {
+    //Builds a tuple, using the primitive form whenever every element is a
+    //primitive so equal tuples always hash the same
+    pub fn tuple(elems: Vec<Binding>) -> Binding {
+        if elems.iter().all(|e| matches!(e, Binding::Primitive(_))) {
+            Binding::Primitive(Primitive::Tuple(
+                elems
+                    .into_iter()
+                    .map(|e| match e {
+                        Binding::Primitive(p) => p,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ))
+        } else {
+            Binding::Tuple(elems)
+        }
+    }
+    pub fn tuple_elems(self) -> Option<Vec<Binding>> {
+        match self {
+            Binding::Tuple(t) => Some(t),
+            Binding::Primitive(Primitive::Tuple(t)) => {
+                Some(t.into_iter().map(Binding::Primitive).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Value for Binding {
+    type Key = Primitive;
+    fn null() -> Self {
+        Binding::Null
+    }
+    fn int(i: i64) -> Self {
+        Primitive::Int(i).into()
+    }
+    fn bool(b: bool) -> Self {
+        Primitive::Bool(b).into()
+    }
+    fn string(s: String) -> Self {
+        Primitive::String_(s).into()
+    }
+    fn array(elems: Vec<Self>) -> Self {
+        Binding::Array(elems)
+    }
+    fn tuple(elems: Vec<Self>) -> Self {
+        Binding::tuple(elems)
+    }
+    fn hash(entries: HashMap<Primitive, Self>) -> Self {
+        Binding::Hash(entries)
+    }
+    fn error(msg: &str) -> Self {
+        Binding::Error(msg.to_string())
+    }
+    fn is_null(&self) -> bool {
+        matches!(self, Binding::Null)
+    }
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Binding::Primitive(Primitive::Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Binding::Primitive(Primitive::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Binding::Primitive(Primitive::String_(s)) => Some(s),
+            _ => None,
+        }
+    }
+    fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Binding::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+    fn as_tuple(&self) -> Option<Vec<Self>> {
+        self.clone().tuple_elems()
+    }
+    fn as_hash(&self) -> Option<&HashMap<Primitive, Self>> {
+        match self {
+            Binding::Hash(h) => Some(h),
+            _ => None,
+        }
+    }
+    fn as_record(&self) -> Option<(&str, &[Self])> {
+        match self {
+            Binding::Record(r) => Some((&r.def.name, &r.fields)),
+            _ => None,
+        }
+    }
+    //compiled functions are primitives too, but not usable as keys
+    fn to_key(&self) -> Option<Primitive> {
+        match self {
+            Binding::Primitive(Primitive::Fn(_) | Primitive::Struct(_)) => None,
+            Binding::Primitive(p) => Some(p.clone()),
+            _ => None,
+        }
+    }
+    fn from_key(key: Primitive) -> Self {
+        Binding::Primitive(key)
+    }
+    fn type_of(&self) -> String {
+        match self {
+            Binding::Primitive(Primitive::Int(_)) => "int",
+            Binding::Primitive(Primitive::String_(_)) => "string",
+            Binding::Primitive(Primitive::Bool(_)) => "bool",
+            Binding::Primitive(Primitive::Tuple(_)) | Binding::Tuple(_) => "tuple",
+            Binding::Null | Binding::Uninit(_) => "null",
+            Binding::Array(_) => "array",
+            Binding::Hash(_) => "hash",
+            Binding::Primitive(Primitive::Fn(_)) | Binding::Closure(_) => "function",
+            Binding::Builtin(_) => "builtin",
+            Binding::Generator(_) => "generator",
+            Binding::Channel(_) => "channel",
+            Binding::Primitive(Primitive::Struct(_)) => "struct",
+            Binding::Record(r) => return r.def.name.clone(),
+            Binding::Error(_) => "error",
+        }
+        .to_string()
+    }
+    fn arity(&self) -> Option<i64> {
+        match self {
+            Binding::Closure(c) if c.fn_.variadic => Some(-1),
+            Binding::Closure(c) => Some(c.fn_.num_args as i64),
+            Binding::Builtin(builtin) => Some(builtin.arity()),
+            Binding::Primitive(Primitive::Struct(def)) => Some(def.fields.len() as i64),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Binding {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Binding::Primitive(p) => write!(f, "{}", p),
-            Binding::Hash(h) => {
-                write!(f, "{{")?;
-                for (k, v) in h {
-                    write!(f, "{}: {}", k, v)?;
-                }
-                write!(f, "}}")
-            }
-            Binding::Array(a) => {
-                write!(f, "[")?;
-                for elem in a {
-                    write!(f, "{},", elem)?;
-                }
-                write!(f, "]")
-            }
+            Binding::Hash(h) if h.is_empty() => write!(f, "{{}}"),
+            Binding::Hash(h) => write!(
+                f,
+                "{{ {} }}",
+                sorted_entries(h)
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Binding::Array(a) => write!(
+                f,
+                "[{}]",
+                a.iter()
+                    .map(|a| format!("{}", a))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Binding::Tuple(t) => write_tuple(f, t),
             Binding::Null => write!(f, "null"),
-            Binding::Builtin(b) => write!(f, "{}", b),
-            Binding::Closure(c) => {
-                write!(f, "closure[")?;
-                write!(f, "fn_bytes{{")?;
-                for b in &c.fn_.body {
-                    write!(f, "{},", b)?;
-                }
-                write!(f, "}}, free: ")?;
-                for b in &c.free {
-                    write!(f, "{},", b)?;
-                }
-                write!(f, "]")
-            }
+            Binding::Generator(_) => write!(f, "generator"),
+            Binding::Channel(_) => write!(f, "channel"),
+            Binding::Uninit(name) => write!(f, "uninitialized {}", name),
+            Binding::Error(e) => write!(f, "error: {}", e),
+            Binding::Record(r) => write!(
+                f,
+                "{} {{ {} }}",
+                r.def.name,
+                r.def
+                    .fields
+                    .iter()
+                    .zip(&r.fields)
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Binding::Builtin(b) => write!(f, "builtin {}", b),
+            Binding::Closure(c) if c.fn_.generator => write!(f, "gen fn/{}", c.fn_.num_args),
+            Binding::Closure(c) => write!(f, "fn/{}", c.fn_.num_args),
         }
     }
 }